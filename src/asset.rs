@@ -0,0 +1,521 @@
+use crate::fatal;
+use crate::writer::Enqueuer;
+use markup5ever_rcdom::{Handle, NodeData};
+use path_clean::PathClean;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Not `manifest.json`, sites often have a web app manifest with that name
+pub const MANIFEST_FILE: &str = "supersolid-manifest.json";
+
+/// Maps the urls of assets to the urls they have been written to
+#[derive(Default)]
+pub struct Manifest {
+    urls: BTreeMap<String, String>,
+    /// Source files of copied assets by their logical url
+    sources: BTreeMap<String, PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Css,
+    Js,
+}
+
+/// Bundles all files matched by `src` into `output`.
+/// Returns the path the bundle was written to, which differs from `output` when fingerprinted.
+pub fn bundle(
+    src: &[String],
+    output: &str,
+    minify: bool,
+    fingerprint: bool,
+    writer: &Enqueuer,
+) -> PathBuf {
+    let kind = match Path::new(output).extension().and_then(|ext| ext.to_str()) {
+        Some("css") => Kind::Css,
+        Some("js") => Kind::Js,
+        _ => fatal!("Assets must be either css or js files; output={}", output),
+    };
+
+    let mut bundler = Bundler {
+        kind,
+        visited: HashSet::new(),
+        hoisted: String::new(),
+    };
+    let mut content = String::new();
+    for pattern in src {
+        let glob = match glob::glob(pattern) {
+            Ok(glob) => glob,
+            Err(err) => fatal!("Unable to glob files; path={}; error={}", pattern, err),
+        };
+        for path in glob {
+            match path {
+                Ok(path) => content.push_str(&bundler.file(&path)),
+                Err(err) => fatal!(
+                    "Unable to obtain path from glob; path={}; error={}",
+                    pattern,
+                    err
+                ),
+            }
+        }
+    }
+    if bundler.visited.is_empty() {
+        tracing::warn!("No files found for asset; output={}", output);
+    }
+    let mut content = bundler.hoisted + &content;
+
+    if minify {
+        content = match kind {
+            Kind::Css => minify_css(&content),
+            Kind::Js => minify_js(&content),
+        };
+    }
+
+    let output = if fingerprint {
+        fingerprinted(Path::new(output), content.as_bytes())
+    } else {
+        PathBuf::from(output)
+    };
+    tracing::info!("Bundled asset; path={}", output.to_string_lossy());
+    writer.file(
+        output.clone(),
+        content,
+        PathBuf::from(
+            src.first()
+                .map(String::as_str)
+                .unwrap_or(crate::CONFIG_FILE),
+        ),
+    );
+    output
+}
+
+/// Inserts a short content hash into the file name; `a/b.css` becomes `a/b.<hash>.css`
+pub fn fingerprinted(path: &Path, content: &[u8]) -> PathBuf {
+    let hash = hash(content);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, hash, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    };
+    path.with_file_name(name)
+}
+
+impl Manifest {
+    pub fn insert(&mut self, logical: &Path, path: &Path) {
+        self.urls.insert(url(logical), url(path));
+    }
+
+    /// Same as `insert`, but also remembers the file the asset was copied from
+    pub fn insert_copy(&mut self, logical: &Path, path: &Path, source: &Path) {
+        self.insert(logical, path);
+        self.sources.insert(url(logical), source.to_path_buf());
+    }
+
+    /// The file a copied asset originates from
+    pub fn source(&self, logical: &str) -> Option<&Path> {
+        self.sources.get(logical).map(PathBuf::as_path)
+    }
+
+    /// Looks up an asset by its logical name, the leading `/` is optional
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.urls.get(&url(Path::new(name))).map(String::as_str)
+    }
+
+    /// Writes the manifest if any asset has been renamed
+    pub fn write(&self, writer: &Enqueuer) {
+        if self.urls.iter().all(|(logical, path)| logical == path) {
+            return;
+        }
+        let json = match serde_json::to_string_pretty(&self.urls) {
+            Ok(json) => json,
+            Err(err) => fatal!("Unable to serialize asset manifest; error={}", err),
+        };
+        writer.file(
+            PathBuf::from(MANIFEST_FILE),
+            json,
+            PathBuf::from(crate::CONFIG_FILE),
+        );
+    }
+
+    /// Rewrites all `src` and `href` attributes in a page that point to renamed assets
+    pub fn rewrite(&self, handle: &Handle, page: &Path) {
+        let dir = page.parent().unwrap_or_else(|| Path::new(""));
+        if let NodeData::Element { attrs, .. } = &handle.data {
+            for attr in attrs.borrow_mut().iter_mut() {
+                if attr.name.local != *"src" && attr.name.local != *"href" {
+                    continue;
+                }
+                if let Some(value) = self.resolve(&attr.value, dir) {
+                    attr.value = value.into();
+                }
+            }
+        }
+        for child in handle.children.borrow().iter() {
+            self.rewrite(child, page);
+        }
+    }
+
+    fn resolve(&self, value: &str, dir: &Path) -> Option<String> {
+        let end = value.find(['?', '#']).unwrap_or(value.len());
+        let (path, suffix) = value.split_at(end);
+        let logical = resolve_url(path, dir)?;
+        let hashed = self
+            .urls
+            .get(&logical)
+            .filter(|hashed| **hashed != logical)?;
+        let new = if path.starts_with('/') {
+            hashed.clone()
+        } else {
+            let name = hashed.rsplit('/').next().unwrap();
+            match path.rfind('/') {
+                Some(i) => format!("{}{}", &path[..=i], name),
+                None => name.to_string(),
+            }
+        };
+        Some(new + suffix)
+    }
+}
+
+/// Resolves a local url relative to the directory of a page, returns `None` for remote urls
+pub fn resolve_url(path: &str, dir: &Path) -> Option<String> {
+    if path.is_empty() || path.starts_with("//") || path.contains(':') {
+        return None;
+    }
+    Some(if path.starts_with('/') {
+        url(Path::new(path))
+    } else {
+        url(&dir.join(path))
+    })
+}
+
+pub fn url(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let clean = PathBuf::from(path.trim_start_matches('/')).clean();
+    format!("/{}", clean.to_string_lossy())
+}
+
+/// Short hex encoded content hash
+pub fn hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+struct Bundler {
+    kind: Kind,
+    /// Files already included, each file is only included once
+    visited: HashSet<PathBuf>,
+    /// Remote css imports, these have to stay at the top of the bundle
+    hoisted: String,
+}
+
+impl Bundler {
+    fn file(&mut self, path: &Path) -> String {
+        let canonical = match path.canonicalize() {
+            Ok(path) => path,
+            Err(err) => fatal!(
+                "Unable to resolve asset; path={}; error={}",
+                path.to_string_lossy(),
+                err
+            ),
+        };
+        if !self.visited.insert(canonical) {
+            return String::new();
+        }
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(err) => fatal!(
+                "Failed to read file; path={}; error={}",
+                path.to_string_lossy(),
+                err
+            ),
+        };
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut out = match self.kind {
+            Kind::Css => self.css_imports(dir, &src),
+            Kind::Js => self.js_imports(dir, &src),
+        };
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out
+    }
+
+    fn css_imports(&mut self, dir: &Path, src: &str) -> String {
+        // Comments are matched as well so imports that are commented out are left alone
+        let re = Regex::new(
+            r#"(?s)/\*.*?\*/|@import\s+(?:url\(\s*)?["']?([^"')\s;]+)["']?\s*\)?\s*([^;]*);"#,
+        )
+        .unwrap();
+        re.replace_all(src, |caps: &regex::Captures| {
+            let target = match caps.get(1) {
+                Some(target) => target.as_str(),
+                None => return caps[0].to_string(),
+            };
+            let media = caps[2].trim();
+            if is_remote(target) {
+                self.hoisted.push_str(&caps[0]);
+                self.hoisted.push('\n');
+                return String::new();
+            }
+            let base = Path::new(target).parent().unwrap_or_else(|| Path::new(""));
+            let inner = rebase_urls(&self.file(&dir.join(target)), base);
+            if media.is_empty() {
+                inner
+            } else {
+                format!("@media {}{{\n{}}}\n", media, inner)
+            }
+        })
+        .to_string()
+    }
+
+    /// Only side effect imports (`import "./a.js";`) can be inlined, everything else is left as is
+    fn js_imports(&mut self, dir: &Path, src: &str) -> String {
+        let re = Regex::new(r#"(?m)^\s*import\s+["']([^"']+)["']\s*;?"#).unwrap();
+        re.replace_all(src, |caps: &regex::Captures| {
+            let target = &caps[1];
+            if is_remote(target) || !target.starts_with('.') {
+                return caps[0].to_string();
+            }
+            self.file(&dir.join(target))
+        })
+        .to_string()
+    }
+}
+
+/// Makes relative `url(...)` references of an imported stylesheet relative to the file importing it
+fn rebase_urls(css: &str, base: &Path) -> String {
+    if base.as_os_str().is_empty() {
+        return css.to_string();
+    }
+    let re = Regex::new(r#"url\(\s*(["']?)([^"')\s]+)["']?\s*\)"#).unwrap();
+    re.replace_all(css, |caps: &regex::Captures| {
+        let target = &caps[2];
+        if target.starts_with(['/', '#']) || target.starts_with("//") || target.contains(':') {
+            return caps[0].to_string();
+        }
+        let rebased = base.join(target).clean();
+        let rebased = rebased.to_string_lossy().replace('\\', "/");
+        format!("url({quote}{}{quote})", rebased, quote = &caps[1])
+    })
+    .to_string()
+}
+
+fn is_remote(target: &str) -> bool {
+    target.starts_with("http:") || target.starts_with("https:") || target.starts_with("//")
+}
+
+fn minify_css(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut space = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for ch in chars.by_ref() {
+                    if prev == '*' && ch == '/' {
+                        break;
+                    }
+                    prev = ch;
+                }
+                // `a/**/b` must not turn into `ab`
+                space = true;
+                continue;
+            }
+            ch if ch.is_whitespace() => {
+                space = true;
+                continue;
+            }
+            _ => (),
+        }
+        // Spaces before `:` are significant in selectors (`a :hover`), so only the ones after go
+        if space
+            && !out.is_empty()
+            && !"{};,>".contains(ch)
+            && !out.ends_with(['{', '}', ';', ',', '>', ':'])
+        {
+            out.push(' ');
+        }
+        space = false;
+        match ch {
+            '"' | '\'' => copy_string(&mut out, ch, &mut chars),
+            '}' if out.ends_with(';') => {
+                out.pop();
+                out.push('}');
+            }
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Only drops comments and indentation, line breaks are kept so automatic semicolon insertion
+/// still works as expected
+fn minify_js(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut line_start = true;
+    while let Some(ch) = chars.next() {
+        if line_start && ch.is_whitespace() {
+            continue;
+        }
+        match ch {
+            '/' if chars.peek() == Some(&'/') => {
+                for ch in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+                trim_line(&mut out);
+                line_start = true;
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                let mut multiline = false;
+                for ch in chars.by_ref() {
+                    if prev == '*' && ch == '/' {
+                        break;
+                    }
+                    multiline |= ch == '\n';
+                    prev = ch;
+                }
+                // The comment still separates tokens, a line break in it may end a statement
+                if multiline {
+                    trim_line(&mut out);
+                    line_start = true;
+                } else if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+                    out.push(' ');
+                }
+                continue;
+            }
+            '/' if regex_allowed(&out) => copy_string(&mut out, '/', &mut chars),
+            '"' | '\'' | '`' => copy_string(&mut out, ch, &mut chars),
+            '\n' => {
+                trim_line(&mut out);
+                line_start = true;
+                continue;
+            }
+            ch => out.push(ch),
+        }
+        line_start = false;
+    }
+    trim_line(&mut out);
+    out
+}
+
+/// Removes trailing whitespace and ends the line unless it is empty
+fn trim_line(out: &mut String) {
+    out.truncate(out.trim_end().len());
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Whether a `/` at this position starts a regex literal rather than being a division
+fn regex_allowed(out: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "return", "typeof", "case", "do", "else", "in", "of", "void", "yield", "await", "delete",
+        "throw", "new",
+    ];
+    let out = out.trim_end();
+    match out.chars().last() {
+        None => true,
+        Some(ch) if ch.is_alphanumeric() || ch == '_' || ch == '$' => {
+            let word = out
+                .rsplit(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '$'))
+                .next()
+                .unwrap_or("");
+            KEYWORDS.contains(&word)
+        }
+        Some(ch) => "(,=:[!&|?{};+-*%<>~^".contains(ch),
+    }
+}
+
+/// Copies a string, template or regex literal verbatim
+fn copy_string(out: &mut String, quote: char, chars: &mut std::iter::Peekable<std::str::Chars>) {
+    out.push(quote);
+    let mut escaped = false;
+    for ch in chars.by_ref() {
+        out.push(ch);
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == quote {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn css_drops_comments_and_whitespace() {
+        assert_eq!(
+            minify_css("a ,\n b > c {\n  color: red ;\n  margin: 0 auto;\n}\n/* done */\n"),
+            "a,b>c{color:red;margin:0 auto}"
+        );
+        assert_eq!(
+            minify_css("a :hover { content: \"a  ;  b\" }"),
+            "a :hover{content:\"a  ;  b\"}"
+        );
+    }
+
+    #[test]
+    fn css_comments_separate_tokens() {
+        assert_eq!(minify_css("a/**/b{margin:0/**/auto}"), "a b{margin:0 auto}");
+    }
+
+    #[test]
+    fn js_drops_comments_and_indentation() {
+        assert_eq!(
+            minify_js("function a() {\n    // comment\n    return 1; // trailing\n}\n"),
+            "function a() {\nreturn 1;\n}\n"
+        );
+        assert_eq!(
+            minify_js("let s = '// not a comment';"),
+            "let s = '// not a comment';\n"
+        );
+        assert_eq!(minify_js("let r = /a\\/\\/b/g;"), "let r = /a\\/\\/b/g;\n");
+    }
+
+    #[test]
+    fn js_block_comments_keep_separators() {
+        // A line break ends the statement without a semicolon, it has to stay
+        assert_eq!(
+            minify_js("let a = 1/*\n*/let b = 2"),
+            "let a = 1\nlet b = 2\n"
+        );
+        assert_eq!(minify_js("return/**/x"), "return x\n");
+    }
+
+    #[test]
+    fn urls_of_imports_are_rebased() {
+        let base = Path::new("sub/dir");
+        assert_eq!(
+            rebase_urls("a{background:url('../img/a.png')}", base),
+            "a{background:url('sub/img/a.png')}"
+        );
+        assert_eq!(
+            rebase_urls(
+                "a{background:url(data:x) url(/a.png) url(//cdn/a.png) url(#id)}",
+                base
+            ),
+            "a{background:url(data:x) url(/a.png) url(//cdn/a.png) url(#id)}"
+        );
+        assert_eq!(
+            rebase_urls("a{b:url(a.png)}", Path::new("")),
+            "a{b:url(a.png)}"
+        );
+    }
+}
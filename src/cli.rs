@@ -0,0 +1,165 @@
+use crate::config::{ProjectConfig, VarSource};
+use crate::scaffold::Starter;
+use crate::{absolute_path, fatal, CONFIG_FILE};
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use tracing::trace;
+use tracing_subscriber::EnvFilter;
+
+/// A simple static site generator.
+///
+/// Exits with 0 on success, 1 if the build failed and 2 on invalid arguments.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    /// Log more, can be repeated
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Build the site into dist
+    Build(BuildArgs),
+    /// Build the site and serve dist over http
+    Serve {
+        #[command(flatten)]
+        project: ProjectArgs,
+        /// Don't minify html, even if enabled in the config
+        #[arg(long)]
+        no_minify: bool,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+    },
+    /// Render everything without writing any output
+    Check(ProjectArgs),
+    /// Create a new project
+    New {
+        path: PathBuf,
+        /// Starter template to create the project from
+        #[arg(short, long, value_enum, default_value = "blog")]
+        template: Starter,
+    },
+    /// Delete dist and cached files
+    Clean(ProjectArgs),
+}
+
+#[derive(Args)]
+pub struct BuildArgs {
+    #[command(flatten)]
+    pub project: ProjectArgs,
+    /// Don't minify html, even if enabled in the config
+    #[arg(long)]
+    pub no_minify: bool,
+    /// Show what would be written without touching dist
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Write a JSON report of every output file to this path
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+    /// Write everything into a `.tar.gz` or `.zip` file instead of dist
+    #[arg(long, value_name = "FILE", conflicts_with = "dry_run")]
+    pub archive: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ProjectArgs {
+    /// Project directory, defaults to the directory of the config file
+    pub dir: Option<PathBuf>,
+    /// Config file, defaults to `config.ron` in the project directory
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Output directory, overrides `dist`
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+    /// Set a variable, overrides `vars`
+    #[arg(short = 'D', long = "define", value_name = "KEY=VALUE", value_parser = parse_define)]
+    pub defines: Vec<(String, String)>,
+}
+
+impl Cli {
+    pub fn init_tracing(&self) {
+        let filter = match (self.quiet, self.verbose) {
+            (true, _) => EnvFilter::new("error"),
+            (false, 0) => {
+                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))
+            }
+            (false, 1) => EnvFilter::new("debug"),
+            _ => EnvFilter::new("trace"),
+        };
+        tracing_subscriber::fmt().with_env_filter(filter).init();
+    }
+}
+
+impl ProjectArgs {
+    /// Changes into the project directory and reads the config.
+    /// Returns the config and the absolute path of the output directory.
+    pub fn load(&self) -> (ProjectConfig, PathBuf) {
+        let dir = self.dir.as_deref().map(absolute);
+        let config_path = match (&self.config, &dir) {
+            (Some(config), _) => absolute(config),
+            (None, Some(dir)) => dir.join(CONFIG_FILE),
+            (None, None) => absolute(Path::new(CONFIG_FILE)),
+        };
+        let out = self.out.as_deref().map(absolute);
+
+        let dir = dir.unwrap_or_else(|| config_path.parent().unwrap().to_path_buf());
+        if let Err(err) = std::env::set_current_dir(&dir) {
+            fatal!(
+                "Unable to set working directory; path={}; error={}",
+                dir.to_string_lossy(),
+                err
+            );
+        }
+        trace!("Set working dir; directory={}", dir.to_string_lossy());
+
+        if !config_path.is_file() {
+            fatal!(
+                "No config file found; path={}",
+                config_path.to_string_lossy()
+            );
+        }
+        let mut config = match std::fs::read_to_string(&config_path)
+            .map(|str| ron::from_str::<ProjectConfig>(&str))
+        {
+            Ok(Ok(config)) => config,
+            Ok(Err(err)) => fatal!("Unable to parse config; error={}", err),
+            Err(err) => fatal!("Unable to read config; error={}", err),
+        };
+        for (key, value) in &self.defines {
+            config
+                .vars
+                .insert(key.clone(), VarSource::Text(value.clone()));
+        }
+
+        let dist = out.unwrap_or_else(|| absolute(&config.dist));
+        (config, dist)
+    }
+}
+
+/// Paths given on the command line are relative to where we were started from, they have to be
+/// made absolute before changing into the project dir
+pub fn absolute(path: &Path) -> PathBuf {
+    match absolute_path(path) {
+        Ok(path) => path,
+        Err(err) => fatal!(
+            "Invalid path provided; path={}; error={}",
+            path.to_string_lossy(),
+            err
+        ),
+    }
+}
+
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    match define.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", define)),
+    }
+}
@@ -1,5 +1,6 @@
 use crate::fatal;
-use std::collections::HashMap;
+use crate::i18n::Language;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::rc::Rc;
 
@@ -8,6 +9,33 @@ pub struct ProjectConfig {
     pub vars: HashMap<String, VarSource>,
     pub dist: PathBuf,
     pub src: Vec<(String, Source)>,
+    #[serde(default)]
+    pub collisions: CollisionPolicy,
+    /// Globs of files in dist that are never removed, even if a previous build produced them
+    #[serde(default)]
+    pub keep: Vec<String>,
+    /// Old path mapped to the url it should redirect to
+    #[serde(default)]
+    pub redirects: BTreeMap<String, String>,
+    #[serde(default)]
+    pub redirect_map: Option<RedirectMap>,
+    /// Minify generated html, can be overridden with `--no-minify`
+    #[serde(default)]
+    pub minify: bool,
+    /// Add a content hash to the names of copied files
+    #[serde(default)]
+    pub fingerprint: bool,
+    #[serde(default)]
+    pub images: Option<ImageConfig>,
+    /// Languages of the site, the first one is the default
+    #[serde(default)]
+    pub languages: Vec<Language>,
+    /// Files that can be used with `super:component`, by name
+    #[serde(default)]
+    pub components: HashMap<String, PathBuf>,
+    /// Every `NAME.html` in here can be used as `super:NAME` or with `super:component`
+    #[serde(default = "default_component_dir")]
+    pub component_dir: PathBuf,
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
@@ -20,10 +48,78 @@ pub enum VarSource {
 pub enum Source {
     Html(String),
     Copy(String),
-    Md { src: String, template: String },
+    Md {
+        src: String,
+        template: String,
+        /// Replace `{{VAR}}` in the text of the pages
+        #[serde(default)]
+        interpolate: bool,
+    },
+    /// Css or js files bundled into a single file
+    Asset {
+        src: Vec<String>,
+        #[serde(default)]
+        minify: bool,
+        #[serde(default)]
+        fingerprint: bool,
+    },
+    /// Scss stylesheet compiled to css
+    Scss {
+        src: String,
+        #[serde(default)]
+        minify: bool,
+        /// Rejected for now, the compiler doesn't track source positions
+        #[serde(default)]
+        source_map: bool,
+        #[serde(default)]
+        load_paths: Vec<PathBuf>,
+    },
     For(String),
 }
 
+/// What to do when two sources produce the same output file
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default)]
+pub enum CollisionPolicy {
+    #[default]
+    Error,
+    Warn,
+}
+
+/// Additional server side redirect file
+#[derive(serde::Deserialize, Debug)]
+pub enum RedirectMap {
+    /// `_redirects` file as used by netlify and others
+    Netlify,
+    /// nginx map file, to be included in a `map` block
+    Nginx(String),
+}
+
+/// Responsive image generation for `img` elements and `super:image`
+#[derive(serde::Deserialize, Debug)]
+pub struct ImageConfig {
+    /// Widths to generate, widths larger than the image itself are skipped
+    pub widths: Vec<u32>,
+    /// Formats generated in addition to the original one
+    #[serde(default)]
+    pub formats: Vec<ImageFormat>,
+    #[serde(default = "default_sizes")]
+    pub sizes: String,
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    /// Output directory for images included with `super:image`
+    #[serde(default = "default_image_out")]
+    pub out: String,
+    /// Encoded images are kept here between builds
+    #[serde(default = "default_image_cache")]
+    pub cache: PathBuf,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+pub enum ImageFormat {
+    Webp,
+    Avif,
+}
+
 #[derive(Default, Clone)]
 pub struct VarStack(Rc<InnerVs>);
 
@@ -33,6 +129,26 @@ struct InnerVs {
     prev: Option<Box<VarStack>>,
 }
 
+fn default_sizes() -> String {
+    "100vw".to_string()
+}
+
+fn default_quality() -> u8 {
+    80
+}
+
+fn default_image_out() -> String {
+    "images".to_string()
+}
+
+fn default_image_cache() -> PathBuf {
+    PathBuf::from(".cache/images")
+}
+
+fn default_component_dir() -> PathBuf {
+    PathBuf::from("components")
+}
+
 impl ProjectConfig {
     pub fn get_stack(&self) -> VarStack {
         VarStack(Rc::new(InnerVs {
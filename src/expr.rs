@@ -0,0 +1,475 @@
+use crate::config::VarStack;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use std::fmt;
+
+/// Result of an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A variable that isn't set
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+impl Value {
+    /// Missing variables, `false`, `0`, empty strings, `"false"` and `"0"` are false.
+    /// Variables are mostly text, so a number in text counts like the number itself.
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty() && s != "false" && self.number() != Some(0.0),
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Str(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Evaluates an expression like `title | truncate(20) | upper` or `servings * 2 > 4`
+pub fn eval(expr: &str, vars: &VarStack) -> Result<Value, String> {
+    let mut parser = ExprParser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+    };
+    let ast = parser.expr()?;
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(format!("Unexpected {:?}", token)),
+        None => ast.eval(vars),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+    Pipe,
+    Comma,
+    Open,
+    Close,
+}
+
+const OPERATORS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '"' || c == '\'' {
+            let end = match rest[1..].find(c) {
+                Some(end) => end + 1,
+                None => return Err("Unclosed string".to_string()),
+            };
+            tokens.push(Token::Str(rest[1..end].to_string()));
+            end + 1
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            match rest[..len].parse() {
+                Ok(num) => tokens.push(Token::Num(num)),
+                Err(_) => return Err(format!("Invalid number {}", &rest[..len])),
+            }
+            len
+        } else if c.is_alphanumeric() || c == '_' {
+            // `read-more` is a name, `a - b` a subtraction
+            let mut len = 0;
+            for (i, c) in rest.char_indices() {
+                let next = rest[i + c.len_utf8()..].chars().next();
+                let part_of_name = c.is_alphanumeric()
+                    || c == '_'
+                    || c == '.'
+                    || (c == '-' && next.is_some_and(char::is_alphanumeric));
+                if !part_of_name {
+                    break;
+                }
+                len = i + c.len_utf8();
+            }
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else {
+            tokens.push(match c {
+                '|' => Token::Pipe,
+                ',' => Token::Comma,
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => return Err(format!("Unexpected character {}", c)),
+            });
+            1
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+enum Expr {
+    Value(Value),
+    Var(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+    Filter(Box<Expr>, String, Vec<Expr>),
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Parses binary operators, from the lowest precedence level on
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        const LEVELS: &[&[&str]] = &[
+            &["||"],
+            &["&&"],
+            &["==", "!=", "<=", ">=", "<", ">"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            if !LEVELS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(0)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Op("!")) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Op("-")) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            _ => self.filters(),
+        }
+    }
+
+    /// Filters bind tighter than operators, `a + b | upper` only changes `b`
+    fn filters(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while let Some(Token::Pipe) = self.peek() {
+            self.pos += 1;
+            let name = match self.next() {
+                Some(Token::Ident(name)) => name,
+                token => return Err(format!("Expected filter name, found {:?}", token)),
+            };
+            let mut args = vec![];
+            if let Some(Token::Open) = self.peek() {
+                self.pos += 1;
+                if let Some(Token::Close) = self.peek() {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        args.push(self.expr()?);
+                        match self.next() {
+                            Some(Token::Comma) => (),
+                            Some(Token::Close) => break,
+                            token => return Err(format!("Expected , or ), found {:?}", token)),
+                        }
+                    }
+                }
+            }
+            expr = Expr::Filter(Box::new(expr), name, args);
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Num(num)) => Ok(Expr::Value(Value::Number(num))),
+            Some(Token::Str(str)) => Ok(Expr::Value(Value::Str(str))),
+            Some(Token::Ident(name)) => Ok(match name.as_str() {
+                "true" => Expr::Value(Value::Bool(true)),
+                "false" => Expr::Value(Value::Bool(false)),
+                _ => Expr::Var(name),
+            }),
+            Some(Token::Open) => {
+                let expr = self.expr()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    token => Err(format!("Expected ), found {:?}", token)),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, vars: &VarStack) -> Result<Value, String> {
+        Ok(match self {
+            Expr::Value(value) => value.clone(),
+            Expr::Var(name) => vars.get(name).map_or(Value::Null, Value::Str),
+            Expr::Not(expr) => Value::Bool(!expr.eval(vars)?.truthy()),
+            Expr::Neg(expr) => Value::Number(-number(&expr.eval(vars)?)?),
+            Expr::Binary(lhs, "&&", rhs) => {
+                Value::Bool(lhs.eval(vars)?.truthy() && rhs.eval(vars)?.truthy())
+            }
+            Expr::Binary(lhs, "||", rhs) => {
+                Value::Bool(lhs.eval(vars)?.truthy() || rhs.eval(vars)?.truthy())
+            }
+            Expr::Binary(lhs, op, rhs) => binary(lhs.eval(vars)?, op, rhs.eval(vars)?)?,
+            Expr::Filter(expr, name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(vars))
+                    .collect::<Result<Vec<_>, _>>()?;
+                filter(expr.eval(vars)?, name, &args)?
+            }
+        })
+    }
+}
+
+fn number(value: &Value) -> Result<f64, String> {
+    value
+        .number()
+        .ok_or_else(|| format!("Not a number: {:?}", value))
+}
+
+/// Numbers are compared and added as numbers, everything else as strings
+fn binary(lhs: Value, op: &str, rhs: Value) -> Result<Value, String> {
+    let numbers = lhs.number().zip(rhs.number());
+    Ok(match op {
+        "+" => match numbers {
+            Some((lhs, rhs)) => Value::Number(lhs + rhs),
+            None => Value::Str(format!("{}{}", lhs, rhs)),
+        },
+        "-" => Value::Number(number(&lhs)? - number(&rhs)?),
+        "*" => Value::Number(number(&lhs)? * number(&rhs)?),
+        "/" => Value::Number(number(&lhs)? / number(&rhs)?),
+        "%" => Value::Number(number(&lhs)? % number(&rhs)?),
+        _ => {
+            let ordering = match numbers {
+                Some((lhs, rhs)) => lhs.partial_cmp(&rhs),
+                None => Some(lhs.to_string().cmp(&rhs.to_string())),
+            };
+            let ordering = match ordering {
+                Some(ordering) => ordering,
+                None => return Ok(Value::Bool(false)),
+            };
+            Value::Bool(match op {
+                "==" => ordering.is_eq(),
+                "!=" => ordering.is_ne(),
+                "<" => ordering.is_lt(),
+                "<=" => ordering.is_le(),
+                ">" => ordering.is_gt(),
+                ">=" => ordering.is_ge(),
+                _ => unreachable!(),
+            })
+        }
+    })
+}
+
+fn filter(value: Value, name: &str, args: &[Value]) -> Result<Value, String> {
+    let arity = match name {
+        "upper" | "lower" | "slugify" | "markdown" => 0,
+        "truncate" | "date" | "default" => 1,
+        _ => return Err(format!("Unknown filter {}", name)),
+    };
+    if args.len() != arity {
+        return Err(format!(
+            "Filter {} takes {} arguments, got {}",
+            name,
+            arity,
+            args.len()
+        ));
+    }
+    Ok(match name {
+        "upper" => Value::Str(value.to_string().to_uppercase()),
+        "lower" => Value::Str(value.to_string().to_lowercase()),
+        "truncate" => {
+            let len = number(&args[0])? as usize;
+            let str = value.to_string();
+            if str.chars().count() <= len {
+                Value::Str(str)
+            } else {
+                let truncated = str.chars().take(len).collect::<String>();
+                Value::Str(format!("{}...", truncated.trim_end()))
+            }
+        }
+        "slugify" => Value::Str(slugify(&value.to_string())),
+        "date" => Value::Str(date(&value.to_string(), &args[0].to_string())?),
+        "markdown" => Value::Str(crate::parser::render_markdown(
+            &value.to_string(),
+            str::to_string,
+        )),
+        "default" => match &value {
+            Value::Null => args[0].clone(),
+            Value::Str(str) if str.is_empty() => args[0].clone(),
+            _ => value,
+        },
+        _ => unreachable!(),
+    })
+}
+
+/// Lowercase letters and digits, separated by single dashes
+fn slugify(str: &str) -> String {
+    let mut slug = String::with_capacity(str.len());
+    for c in str.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Formats a `2021-03-04`, `2021-03-04 12:30:00` or RFC 3339 date with strftime syntax
+fn date(str: &str, format: &str) -> Result<String, String> {
+    let items = StrftimeItems::new(format).collect::<Vec<_>>();
+    if items.contains(&Item::Error) {
+        return Err(format!("Invalid date format {}", format));
+    }
+    let str = str.trim();
+    let date = match DateTime::parse_from_rfc3339(str) {
+        Ok(date) => date.naive_local(),
+        Err(_) => match NaiveDateTime::parse_from_str(str, "%Y-%m-%d %H:%M:%S") {
+            Ok(date) => date,
+            Err(_) => match NaiveDate::parse_from_str(str, "%Y-%m-%d") {
+                Ok(date) => date.and_hms_opt(0, 0, 0).unwrap(),
+                Err(err) => return Err(format!("Invalid date {}: {}", str, err)),
+            },
+        },
+    };
+    Ok(date.format_with_items(items.into_iter()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_with(expr: &str, vars: &[(&str, &str)]) -> Result<Value, String> {
+        let vars = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        eval(expr, &VarStack::default().combine(vars))
+    }
+
+    fn str(expr: &str, vars: &[(&str, &str)]) -> String {
+        eval_with(expr, vars).unwrap().to_string()
+    }
+
+    #[test]
+    fn arithmetic_and_precedence() {
+        assert_eq!(str("1 + 2 * 3", &[]), "7");
+        assert_eq!(str("(1 + 2) * 3", &[]), "9");
+        assert_eq!(str("servings * 2", &[("servings", "4")]), "8");
+        assert_eq!(str("7 / 2", &[]), "3.5");
+        assert_eq!(str("-3 + 1", &[]), "-2");
+        assert_eq!(str("'a' + 1", &[]), "a1");
+    }
+
+    #[test]
+    fn names_with_dashes() {
+        assert_eq!(str("read-more", &[("read-more", "x")]), "x");
+        assert_eq!(str("a - b", &[("a", "3"), ("b", "1")]), "2");
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(str("10 > 9", &[]), "true");
+        assert_eq!(str("'10' > '9'", &[]), "true");
+        assert_eq!(str("'b' > 'a'", &[]), "true");
+        assert_eq!(str("tag == 'rust' && !missing", &[("tag", "rust")]), "true");
+    }
+
+    #[test]
+    fn truthiness() {
+        let truthy = |expr: &str, vars: &[(&str, &str)]| eval_with(expr, vars).unwrap().truthy();
+        assert!(!truthy("missing", &[]));
+        assert!(!truthy("v", &[("v", "")]));
+        assert!(!truthy("v", &[("v", "false")]));
+        assert!(!truthy("v", &[("v", "0")]));
+        assert!(!truthy("v", &[("v", "0.0")]));
+        assert!(!truthy("1 - 1", &[]));
+        assert!(truthy("v", &[("v", "00x")]));
+        assert!(truthy("v", &[("v", "1")]));
+        assert!(truthy("v", &[("v", "no")]));
+    }
+
+    #[test]
+    fn filters() {
+        let vars = &[("title", "Hello, World!"), ("date", "2021-03-04")];
+        assert_eq!(str("title | upper", vars), "HELLO, WORLD!");
+        assert_eq!(str("title | lower", vars), "hello, world!");
+        assert_eq!(str("title | slugify", vars), "hello-world");
+        assert_eq!(str("title | truncate(5)", vars), "Hello...");
+        assert_eq!(str("title | truncate(50)", vars), "Hello, World!");
+        assert_eq!(str("date | date('%d.%m.%Y')", vars), "04.03.2021");
+        assert_eq!(
+            str("'2021-03-04T12:30:00+01:00' | date('%H:%M')", &[]),
+            "12:30"
+        );
+        assert_eq!(str("missing | default('x')", vars), "x");
+        assert_eq!(str("title | default('x')", vars), "Hello, World!");
+        assert_eq!(str("'*a*' | markdown", &[]), "<p><em>a</em></p>\n");
+        assert_eq!(str("title | slugify | upper", vars), "HELLO-WORLD");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(eval_with("title | nope", &[]).is_err());
+        assert!(eval_with("title | truncate", &[]).is_err());
+        assert!(eval_with("'unclosed", &[]).is_err());
+        assert!(eval_with("1 +", &[]).is_err());
+        assert!(eval_with("a b", &[]).is_err());
+        assert!(eval_with("'x' * 2", &[]).is_err());
+        assert!(eval_with("'tomorrow' | date('%Y')", &[]).is_err());
+    }
+}
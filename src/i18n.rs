@@ -0,0 +1,85 @@
+use crate::fatal;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Language {
+    pub code: String,
+    /// Strings available to templates as variables
+    #[serde(default)]
+    pub strings: HashMap<String, String>,
+}
+
+/// All languages of a site, the first one is the default and is written to the root of dist
+pub struct Languages(Vec<Language>);
+
+impl Languages {
+    pub fn new(languages: Vec<Language>) -> Self {
+        Self(languages)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Language> {
+        self.0.iter().find(|lang| lang.code == code)
+    }
+
+    pub fn default_language(&self) -> Option<&Language> {
+        self.0.first()
+    }
+
+    /// Language code in a file name, `recipe.de.md` is german
+    pub fn of_path(&self, path: &Path) -> Option<&Language> {
+        let stem = Path::new(path.file_stem()?);
+        let code = stem.extension()?.to_str()?;
+        self.get(code)
+    }
+
+    /// Language of a page, set by the `lang` front matter or by its file name
+    pub fn detect(
+        &self,
+        source: &Path,
+        front_matter: Option<&HashMap<String, String>>,
+    ) -> Option<&Language> {
+        match front_matter.and_then(|vars| vars.get("lang")) {
+            Some(code) => match self.get(code) {
+                Some(lang) => Some(lang),
+                None => fatal!(
+                    "Unknown language; lang={}; path={}",
+                    code,
+                    source.to_string_lossy()
+                ),
+            },
+            None => self.of_path(source).or_else(|| self.default_language()),
+        }
+    }
+
+    /// Variables of a language, `lang` and its strings
+    pub fn vars(&self, lang: &Language) -> HashMap<String, String> {
+        let mut vars = lang.strings.clone();
+        vars.insert("lang".to_string(), lang.code.clone());
+        vars
+    }
+
+    /// Removes a language code from the file name of a path, `a/b.de.html` becomes `a/b.html`
+    pub fn strip(&self, path: &Path) -> PathBuf {
+        if self.of_path(path).is_none() {
+            return path.to_path_buf();
+        }
+        let stem = Path::new(path.file_stem().unwrap()).file_stem().unwrap();
+        let mut name = stem.to_os_string();
+        if let Some(ext) = path.extension() {
+            name.push(".");
+            name.push(ext);
+        }
+        path.with_file_name(name)
+    }
+
+    /// Where a page in this language is written to, anything but the default language goes into
+    /// a directory named after its code
+    pub fn localize(&self, path: &Path, lang: &Language) -> PathBuf {
+        let path = self.strip(path);
+        match self.default_language() {
+            Some(default) if default.code != lang.code => Path::new(&lang.code).join(path),
+            _ => path,
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::asset::{resolve_url, url, Manifest};
+use crate::config::{ImageConfig, ImageFormat};
+use crate::fatal;
+use crate::processor::{attribute, element, element_name};
+use crate::writer::Enqueuer;
+use html5ever::Attribute;
+use image::{DynamicImage, GenericImageView};
+use markup5ever_rcdom::{Handle, NodeData};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Generates resized and converted variants of images
+pub struct Images {
+    cfg: Option<ImageConfig>,
+    /// Whether encoded images are stored in the cache dir
+    cache: bool,
+    writer: Enqueuer,
+    /// Images already written, every image is only processed once per build
+    done: RefCell<HashMap<PathBuf, Rc<Variants>>>,
+}
+
+struct Variants {
+    width: u32,
+    height: u32,
+    /// Url of the largest variant in the original format
+    src: String,
+    /// Mime type and srcset of every format, the original format is last
+    sets: Vec<(&'static str, String)>,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Jpeg,
+    Png,
+    Webp,
+    Avif,
+}
+
+impl Images {
+    pub fn new(cfg: Option<ImageConfig>, cache: bool, writer: Enqueuer) -> Self {
+        if let Some(cfg) = &cfg {
+            if !cfg!(feature = "avif") && cfg.formats.contains(&ImageFormat::Avif) {
+                fatal!("Avif images requested, but supersolid was built without avif support");
+            }
+        }
+        Self {
+            cfg,
+            cache,
+            writer,
+            done: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a srcset and dimensions to all `img` elements showing copied images
+    pub fn rewrite(&self, handle: &Handle, page: &Path, assets: &Manifest) {
+        if self.cfg.is_none() {
+            return;
+        }
+        let dir = page.parent().unwrap_or_else(|| Path::new(""));
+        let children = handle.children.borrow().clone();
+        for (i, child) in children.into_iter().enumerate() {
+            if let Some((source, logical)) = Self::copied_image(&child, assets, dir) {
+                if element_name(handle) != "picture" {
+                    let out_dir = Path::new(logical.trim_start_matches('/')).parent().unwrap();
+                    let variants = self.variants(source, out_dir);
+                    handle.children.borrow_mut()[i] = self.apply(child, &variants);
+                    continue;
+                }
+            }
+            self.rewrite(&child, page, assets);
+        }
+    }
+
+    /// Expands `super:image`, `src` is the path of the source image instead of an url
+    pub fn element(&self, src: &Path, mut attrs: Vec<Attribute>) -> Handle {
+        let cfg = match &self.cfg {
+            Some(cfg) => cfg,
+            None => fatal!("super:image used, but images aren't configured"),
+        };
+        if !is_supported(src) {
+            fatal!("Unsupported image format; path={}", src.to_string_lossy());
+        }
+        let variants = self.variants(src, Path::new(&cfg.out));
+        attrs.push(attribute("src", &variants.src));
+        self.apply(element("img", attrs, vec![]), &variants)
+    }
+
+    fn copied_image<'a>(
+        handle: &Handle,
+        assets: &'a Manifest,
+        dir: &Path,
+    ) -> Option<(&'a Path, String)> {
+        if element_name(handle) != "img" {
+            return None;
+        }
+        let attrs = match &handle.data {
+            NodeData::Element { attrs, .. } => attrs.borrow(),
+            _ => return None,
+        };
+        if attrs.iter().any(|attr| attr.name.local == *"srcset") {
+            return None;
+        }
+        let src = attrs.iter().find(|attr| attr.name.local == *"src")?;
+        let end = src.value.find(['?', '#']).unwrap_or(src.value.len());
+        let logical = resolve_url(&src.value[..end], dir)?;
+        let source = assets
+            .source(&logical)
+            .filter(|source| is_supported(source))?;
+        Some((source, logical))
+    }
+
+    fn apply(&self, img: Handle, variants: &Variants) -> Handle {
+        let cfg = self.cfg.as_ref().unwrap();
+        if let NodeData::Element { attrs, .. } = &img.data {
+            let mut attrs = attrs.borrow_mut();
+            let has =
+                |attrs: &[Attribute], name: &str| attrs.iter().any(|attr| attr.name.local == *name);
+            if !has(&attrs, "width") && !has(&attrs, "height") {
+                attrs.push(attribute("width", &variants.width.to_string()));
+                attrs.push(attribute("height", &variants.height.to_string()));
+            }
+            if !has(&attrs, "sizes") {
+                attrs.push(attribute("sizes", &cfg.sizes));
+            }
+            attrs.push(attribute("srcset", &variants.sets.last().unwrap().1));
+        }
+        if variants.sets.len() == 1 {
+            return img;
+        }
+
+        let sizes = match &img.data {
+            NodeData::Element { attrs, .. } => attrs
+                .borrow()
+                .iter()
+                .find(|attr| attr.name.local == *"sizes")
+                .map(|attr| attr.value.to_string())
+                .unwrap(),
+            _ => unreachable!(),
+        };
+        let mut children = variants.sets[..variants.sets.len() - 1]
+            .iter()
+            .map(|(mime, srcset)| {
+                element(
+                    "source",
+                    vec![
+                        attribute("type", mime),
+                        attribute("srcset", srcset),
+                        attribute("sizes", &sizes),
+                    ],
+                    vec![],
+                )
+            })
+            .collect::<Vec<_>>();
+        children.push(img);
+        element("picture", vec![], children)
+    }
+
+    fn variants(&self, source: &Path, out_dir: &Path) -> Rc<Variants> {
+        if let Some(variants) = self.done.borrow().get(source) {
+            return variants.clone();
+        }
+        let cfg = self.cfg.as_ref().unwrap();
+
+        let data = match std::fs::read(source) {
+            Ok(data) => data,
+            Err(err) => fatal!(
+                "Failed to read file; path={}; error={}",
+                source.to_string_lossy(),
+                err
+            ),
+        };
+        let img = match image::load_from_memory(&data) {
+            Ok(img) => img,
+            Err(err) => fatal!(
+                "Unable to decode image; path={}; error={}",
+                source.to_string_lossy(),
+                err
+            ),
+        };
+        let hash = crate::asset::hash(&data);
+        let (width, height) = img.dimensions();
+
+        let mut widths = cfg
+            .widths
+            .iter()
+            .copied()
+            .filter(|w| *w < width)
+            .collect::<Vec<_>>();
+        widths.push(width);
+        widths.sort_unstable();
+        widths.dedup();
+
+        let mut formats = cfg
+            .formats
+            .iter()
+            .map(|format| match format {
+                ImageFormat::Webp => Format::Webp,
+                ImageFormat::Avif => Format::Avif,
+            })
+            .collect::<Vec<_>>();
+        formats.push(match extension(source).as_str() {
+            "png" => Format::Png,
+            _ => Format::Jpeg,
+        });
+
+        if self.cache {
+            if let Err(err) = std::fs::create_dir_all(&cfg.cache) {
+                fatal!(
+                    "Unable to create image cache; path={}; error={}",
+                    cfg.cache.to_string_lossy(),
+                    err
+                );
+            }
+        }
+        // The hash keeps images with the same name from different directories apart
+        let stem = format!(
+            "{}-{}",
+            source.file_stem().unwrap().to_string_lossy(),
+            &hash[..8]
+        );
+        let mut sets = vec![vec![]; formats.len()];
+        let mut src = String::new();
+        for w in widths {
+            let mut resized = None;
+            for (format, set) in formats.iter().zip(sets.iter_mut()) {
+                let cached = cfg.cache.join(format!(
+                    "{}-{}-{}.{}",
+                    hash,
+                    w,
+                    cfg.quality,
+                    format.extension()
+                ));
+                let out = out_dir.join(format!("{}-{}.{}", stem, w, format.extension()));
+                src = url(&out);
+                set.push(format!("{} {}w", src, w));
+                if cached.exists() {
+                    self.writer.copy(cached, out);
+                    continue;
+                }
+                let resized = resized.get_or_insert_with(|| {
+                    if w == width {
+                        img.clone()
+                    } else {
+                        img.resize(w, u32::MAX, image::imageops::FilterType::Lanczos3)
+                    }
+                });
+                tracing::trace!(
+                    "Encoding image; path={}; width={}; format={}",
+                    source.to_string_lossy(),
+                    w,
+                    format.extension()
+                );
+                let encoded = format.encode(resized, cfg.quality);
+                if !self.cache {
+                    self.writer.file(out, encoded, source.to_path_buf());
+                    continue;
+                }
+                if let Err(err) = std::fs::write(&cached, encoded) {
+                    fatal!(
+                        "Unable to write cached image; path={}; error={}",
+                        cached.to_string_lossy(),
+                        err
+                    );
+                }
+                self.writer.copy(cached, out);
+            }
+        }
+
+        let variants = Rc::new(Variants {
+            width,
+            height,
+            src,
+            sets: formats
+                .iter()
+                .zip(sets)
+                .map(|(format, set)| (format.mime(), set.join(", ")))
+                .collect(),
+        });
+        self.done
+            .borrow_mut()
+            .insert(source.to_path_buf(), variants.clone());
+        variants
+    }
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpg",
+            Format::Png => "png",
+            Format::Webp => "webp",
+            Format::Avif => "avif",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::Webp => "image/webp",
+            Format::Avif => "image/avif",
+        }
+    }
+
+    fn encode(self, img: &DynamicImage, quality: u8) -> Vec<u8> {
+        let mut buf = vec![];
+        let res = match self {
+            Format::Jpeg => image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buf, quality)
+                .encode_image(&img.to_rgb8()),
+            Format::Png => img.write_to(&mut Cursor::new(&mut buf), image::ImageFormat::Png),
+            Format::Webp => {
+                let rgba = img.to_rgba8();
+                let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                    .encode(quality as f32);
+                buf.extend_from_slice(&encoded);
+                Ok(())
+            }
+            #[cfg(feature = "avif")]
+            Format::Avif => {
+                use image::ImageEncoder;
+                let rgba = img.to_rgba8();
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut buf, 8, quality)
+                    .write_image(
+                        &rgba,
+                        rgba.width(),
+                        rgba.height(),
+                        image::ExtendedColorType::Rgba8,
+                    )
+            }
+            #[cfg(not(feature = "avif"))]
+            Format::Avif => unreachable!(),
+        };
+        if let Err(err) = res {
+            fatal!(
+                "Unable to encode image; format={}; error={}",
+                self.extension(),
+                err
+            );
+        }
+        buf
+    }
+}
+
+fn is_supported(path: &Path) -> bool {
+    matches!(extension(path).as_str(), "jpg" | "jpeg" | "png")
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
@@ -0,0 +1,339 @@
+use std::path::{Path, PathBuf};
+
+use crate::asset::Manifest;
+use crate::cli::ProjectArgs;
+use crate::config::Source;
+use crate::i18n::Languages;
+use crate::images::Images;
+use crate::pages::{Page, Pages};
+use crate::processor::{HtmlProcessor, MarkdownProcessor, Processor};
+use crate::redirect::Redirects;
+use crate::report::{PageStats, Report};
+use crate::writer::Target;
+use path_clean::PathClean;
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::{info, warn};
+
+mod asset;
+pub mod cli;
+mod config;
+mod expr;
+mod i18n;
+mod images;
+mod minify;
+mod pages;
+mod parser;
+mod processor;
+mod redirect;
+pub mod report;
+pub mod scaffold;
+mod scss;
+pub mod serve;
+pub mod sink;
+mod staging;
+pub mod writer;
+
+pub const CONFIG_FILE: &str = "config.ron";
+
+#[macro_export]
+macro_rules! fatal {
+    ($($token:tt)*) => {{
+        tracing::error!($($token)*);
+        ::std::process::exit(1);
+    }};
+}
+
+/// Builds the project into the target for its dist.
+/// Returns the output directory and what was written.
+pub fn build(
+    args: &ProjectArgs,
+    no_minify: bool,
+    target: impl FnOnce(PathBuf) -> Target,
+) -> (PathBuf, Report) {
+    let (config, dist) = args.load();
+
+    let keep = config
+        .keep
+        .iter()
+        .map(|keep| match glob::Pattern::new(keep) {
+            Ok(pattern) => pattern,
+            Err(err) => fatal!("Invalid keep pattern; pattern={}; error={}", keep, err),
+        })
+        .collect();
+    let target = target(dist.clone());
+    // Builds that don't change dist shouldn't leave anything behind either
+    let cache = !matches!(target, Target::DryRun(_) | Target::Memory(_));
+    let (handle, writer) = writer::Writer::new(target, config.collisions, keep);
+
+    let minify = config.minify && !no_minify;
+    let mut redirects = Redirects::new(writer.clone());
+    for (from, to) in config.redirects.iter() {
+        redirects.add(from, to, PathBuf::from(CONFIG_FILE));
+    }
+
+    // Assets are handled first, so pages can reference their final names
+    let mut assets = Manifest::default();
+    for (output, src) in config.src.iter() {
+        match src {
+            Source::Copy(src) => {
+                let glob = match glob::glob(src) {
+                    Ok(glob) => glob,
+                    Err(err) => fatal!("Unable to glob files; path={}; error={}", src, err),
+                };
+                glob.map(|path| match path {
+                    Ok(path) => path,
+                    Err(err) => fatal!(
+                        "Unable to obtain path from glob; path={}; error={}",
+                        src,
+                        err
+                    ),
+                })
+                .map(|path| match path.file_name() {
+                    Some(name) => (path.to_path_buf(), Path::new(output).join(Path::new(name))),
+                    None => fatal!(
+                        "Path contains no file name; path={}",
+                        path.to_string_lossy()
+                    ),
+                })
+                .for_each(|(from, to)| {
+                    let hashed = if config.fingerprint {
+                        match std::fs::read(&from) {
+                            Ok(data) => asset::fingerprinted(&to, &data),
+                            Err(err) => fatal!(
+                                "Failed to read file; path={}; error={}",
+                                from.to_string_lossy(),
+                                err
+                            ),
+                        }
+                    } else {
+                        to.clone()
+                    };
+                    assets.insert_copy(&to, &hashed, &from);
+                    writer.copy(from, hashed)
+                });
+            }
+            Source::Asset {
+                src,
+                minify,
+                fingerprint,
+            } => {
+                let path = asset::bundle(src, output, *minify, *fingerprint, &writer);
+                assets.insert(Path::new(output), &path);
+            }
+            Source::Scss {
+                src,
+                minify,
+                source_map,
+                load_paths,
+            } => {
+                if *source_map {
+                    fatal!(
+                        "Scss source maps aren't supported, the compiler doesn't track source positions; src={}",
+                        src
+                    );
+                }
+                let path = scss::compile(
+                    src,
+                    output,
+                    *minify,
+                    config.fingerprint,
+                    load_paths,
+                    &writer,
+                );
+                assets.insert(Path::new(output), &path);
+            }
+            _ => (),
+        }
+    }
+    assets.write(&writer);
+
+    let mut var_stack = config.get_stack(); // TODO
+    let languages = Languages::new(config.languages);
+    if let Some(lang) = languages.default_language() {
+        var_stack = var_stack.combine(languages.vars(lang));
+    }
+    let images = Images::new(config.images, cache, writer.clone());
+    let components = processor::components(config.components, &config.component_dir);
+    let mut pages = Pages {
+        writer: writer.clone(),
+        redirects,
+        minify,
+        assets: &assets,
+        images: &images,
+        languages: &languages,
+        rendered: vec![],
+        stats: HashMap::new(),
+    };
+    for (output, src) in config.src.into_iter() {
+        let cfg = processor::ProcessorConfig {
+            vars: var_stack.clone(),
+            assets: &assets,
+            images: &images,
+            languages: &languages,
+            components: &components,
+        };
+        match src {
+            Source::Html(src) => process(
+                src,
+                &output,
+                &mut pages,
+                HtmlProcessor {
+                    cfg,
+                    stack: vec![],
+                    content: vec![],
+                    parent: None,
+                    read: vec![],
+                },
+            ),
+            Source::Md {
+                src,
+                template,
+                interpolate,
+            } => process(
+                src,
+                &output,
+                &mut pages,
+                MarkdownProcessor {
+                    cfg,
+                    template,
+                    front_matter: HashMap::new(),
+                    read: vec![],
+                    stack: vec![],
+                    interpolate,
+                },
+            ),
+            Source::Copy(_) | Source::Asset { .. } | Source::Scss { .. } => (),
+            Source::For(src) => fatal!("'For' not implemented yet; src={}", src), // TODO
+        }
+    }
+
+    pages.write();
+    if let Some(map) = &config.redirect_map {
+        pages.redirects.write_map(map);
+    }
+
+    let stats = std::mem::take(&mut pages.stats);
+    drop(pages);
+    drop(images);
+    drop(writer);
+    let mut report = handle.join();
+    report.add_pages(stats);
+    (dist, report)
+}
+
+/// Deletes the output directory and the image cache
+pub fn clean(args: &ProjectArgs) {
+    let (config, dist) = args.load();
+    let mut dirs = vec![staging::staging_path(&dist), dist];
+    if let Some(images) = config.images {
+        dirs.push(images.cache);
+    }
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        info!("Deleting {}", dir.to_string_lossy());
+        if let Err(err) = std::fs::remove_dir_all(dir) {
+            fatal!(
+                "Unable to delete dir; path={}; error={}",
+                dir.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
+
+fn process<P: Processor>(src: String, output: &str, pages: &mut Pages, mut p: P) {
+    let glob = match glob::glob(&src) {
+        Ok(glob) => glob,
+        Err(err) => fatal!("Unable to glob files; path={}; error={}", src, err),
+    };
+    let files = glob
+        .map(|path| match path {
+            Ok(path) => path,
+            Err(err) => fatal!(
+                "Unable to obtain path from glob; path={}; error={}",
+                src,
+                err
+            ),
+        })
+        .collect::<Vec<_>>();
+    let out_files = match files.len() {
+        0 => {
+            warn!("No files found, skipping; path={}", src);
+            return;
+        }
+        1 => vec![output.to_string()],
+        _ => files
+            .iter()
+            .map(|path| {
+                Path::new(output)
+                    .join(match path.file_name() {
+                        Some(name) => PathBuf::from(name),
+                        None => fatal!(
+                            "Path contains no file name; path={}",
+                            path.to_string_lossy()
+                        ),
+                    })
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .map(|mut file| {
+                if file.ends_with(".md") {
+                    file = format!("{}.html", &file[..file.len() - 3]);
+                }
+                file
+            })
+            .collect(),
+    };
+    for (source, output) in files
+        .into_iter()
+        .zip(out_files.into_iter().map(PathBuf::from))
+    {
+        let start = Instant::now();
+        let handle = p.process(&source);
+        let stats = PageStats {
+            render_time: start.elapsed(),
+            dependencies: p.dependencies(&source),
+        };
+        let front_matter = p.front_matter(&source);
+        let aliases = front_matter
+            .and_then(|vars| vars.get("aliases"))
+            .map(|aliases| {
+                aliases
+                    .split(',')
+                    .filter(|alias| !alias.trim().is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let lang = pages.languages.detect(&source, front_matter);
+        let key = pages.languages.strip(&output);
+        let output = match lang {
+            Some(lang) => pages.languages.localize(&output, lang),
+            None => output,
+        };
+
+        pages.rendered.push(Page {
+            handle,
+            source,
+            output,
+            key,
+            lang: lang.map(|lang| lang.code.clone()),
+            aliases,
+            stats,
+        });
+    }
+}
+
+// Taken from https://stackoverflow.com/a/54817755
+fn absolute_path(path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+    let path = path.as_ref();
+
+    let absolute_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    }
+    .clean();
+
+    Ok(absolute_path)
+}
@@ -0,0 +1,320 @@
+use crate::processor::element_name;
+use html5ever::{namespace_url, ns, QualName};
+use markup5ever_rcdom::{Handle, NodeData};
+
+/// Elements whose whitespace is significant
+const PRESERVE: &[&str] = &["pre", "textarea", "script", "style"];
+/// Elements in which whitespace only text nodes are never rendered
+const DROP_WHITESPACE: &[&str] = &[
+    "html", "head", "table", "thead", "tbody", "tfoot", "tr", "colgroup", "ul", "ol", "dl",
+    "select", "optgroup",
+];
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr", "basefont", "bgsound", "frame", "keygen",
+];
+const RAW_TEXT: &[&str] = &[
+    "style",
+    "script",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+    "noscript",
+];
+/// Elements after which a `</p>` can be omitted
+const CLOSES_P: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Drops comments and collapses whitespace
+pub fn minify(handle: &Handle) {
+    let name = element_name(handle);
+    if PRESERVE.contains(&name) {
+        return;
+    }
+    let children = handle.children.take();
+    let mut new = Vec::with_capacity(children.len());
+    for child in children {
+        match &child.data {
+            NodeData::Comment { .. } => continue,
+            NodeData::Text { contents } => {
+                let collapsed = collapse(&contents.borrow());
+                if collapsed.is_empty() || (collapsed == " " && DROP_WHITESPACE.contains(&name)) {
+                    continue;
+                }
+                *contents.borrow_mut() = collapsed.into();
+            }
+            NodeData::Element { .. } => minify(&child),
+            _ => (),
+        }
+        new.push(child);
+    }
+    *handle.children.borrow_mut() = new;
+}
+
+/// Serializes a document while leaving out optional quotes and end tags
+pub fn serialize(handle: &Handle) -> String {
+    let mut out = String::new();
+    write_node(&mut out, handle, None);
+    out
+}
+
+fn write_node(out: &mut String, handle: &Handle, parent: Option<&Handle>) {
+    match &handle.data {
+        NodeData::Document => {
+            for child in handle.children.borrow().iter() {
+                write_node(out, child, Some(handle));
+            }
+        }
+        NodeData::Doctype { name, .. } => {
+            out.push_str("<!DOCTYPE ");
+            out.push_str(name);
+            out.push('>');
+        }
+        NodeData::Text { contents } => {
+            let parent = parent.map(element_name).unwrap_or("");
+            if RAW_TEXT.contains(&parent) {
+                out.push_str(&contents.borrow());
+            } else {
+                escape(out, &contents.borrow(), false);
+            }
+        }
+        NodeData::Comment { contents } => {
+            out.push_str("<!--");
+            out.push_str(contents);
+            out.push_str("-->");
+        }
+        NodeData::ProcessingInstruction { target, contents } => {
+            out.push_str("<?");
+            out.push_str(target);
+            out.push(' ');
+            out.push_str(contents);
+            out.push('>');
+        }
+        NodeData::Element {
+            name,
+            attrs,
+            template_contents,
+            ..
+        } => {
+            out.push('<');
+            out.push_str(&name.local);
+            for attr in attrs.borrow().iter() {
+                out.push(' ');
+                write_attr_name(out, &attr.name);
+                if attr.value.is_empty() {
+                    continue;
+                }
+                out.push('=');
+                if attr
+                    .value
+                    .contains(|ch: char| ch.is_ascii_whitespace() || "\"'=<>`".contains(ch))
+                {
+                    out.push('"');
+                    escape(out, &attr.value, true);
+                    out.push('"');
+                } else {
+                    escape(out, &attr.value, true);
+                }
+            }
+            out.push('>');
+
+            if VOID.contains(&&*name.local) {
+                return;
+            }
+            // The parser drops a leading newline in these, so it has to be doubled
+            if matches!(&*name.local, "pre" | "textarea" | "listing") {
+                if let Some(NodeData::Text { contents }) =
+                    handle.children.borrow().first().map(|child| &child.data)
+                {
+                    if contents.borrow().starts_with('\n') {
+                        out.push('\n');
+                    }
+                }
+            }
+            let children = match template_contents {
+                Some(contents) => contents.children.borrow(),
+                None => handle.children.borrow(),
+            };
+            for child in children.iter() {
+                write_node(out, child, Some(handle));
+            }
+
+            if !end_tag_optional(handle, parent) {
+                out.push_str("</");
+                out.push_str(&name.local);
+                out.push('>');
+            }
+        }
+    }
+}
+
+fn write_attr_name(out: &mut String, name: &QualName) {
+    match name.ns {
+        ns!() => (),
+        ns!(xml) => out.push_str("xml:"),
+        ns!(xmlns) if &*name.local != "xmlns" => out.push_str("xmlns:"),
+        ns!(xlink) => out.push_str("xlink:"),
+        _ => (),
+    }
+    out.push_str(&name.local);
+}
+
+fn end_tag_optional(handle: &Handle, parent: Option<&Handle>) -> bool {
+    let parent = match parent {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let siblings = parent.children.borrow();
+    let next = siblings
+        .iter()
+        .position(|sibling| std::rc::Rc::ptr_eq(sibling, handle))
+        .and_then(|i| siblings.get(i + 1));
+    let next_name = next.map(element_name);
+    let is_last = next.is_none();
+    let next_is = |names: &[&str]| next_name.is_some_and(|name| names.contains(&name));
+
+    match element_name(handle) {
+        "html" | "body" => true,
+        "head" => match next.map(|next| &next.data) {
+            Some(NodeData::Text { contents }) => !contents
+                .borrow()
+                .starts_with(|ch: char| ch.is_ascii_whitespace()),
+            _ => true,
+        },
+        "li" => is_last || next_is(&["li"]),
+        "dt" => next_is(&["dt", "dd"]),
+        "dd" => is_last || next_is(&["dt", "dd"]),
+        "option" => is_last || next_is(&["option", "optgroup"]),
+        "tr" => is_last || next_is(&["tr"]),
+        "td" | "th" => is_last || next_is(&["td", "th"]),
+        "p" => {
+            next_is(CLOSES_P)
+                || (is_last
+                    && !matches!(
+                        element_name(parent),
+                        "a" | "audio" | "del" | "ins" | "map" | "noscript" | "video"
+                    ))
+        }
+        _ => false,
+    }
+}
+
+fn collapse(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for ch in text.chars() {
+        if ch.is_ascii_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            out.push(' ');
+            space = false;
+        }
+        out.push(ch);
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+fn escape(out: &mut String, text: &str, attr: bool) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '\u{00A0}' => out.push_str("&nbsp;"),
+            '"' if attr => out.push_str("&quot;"),
+            '<' if !attr => out.push_str("&lt;"),
+            '>' if !attr => out.push_str("&gt;"),
+            ch => out.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minified(html: &str) -> String {
+        let doc = crate::parser::parse_document(html);
+        minify(&doc);
+        serialize(&doc)
+    }
+
+    #[test]
+    fn collapses_whitespace() {
+        assert_eq!(
+            minified("<!DOCTYPE html>\n<html>\n  <head>\n    <title>A  page</title>\n  </head>\n  <body>\n    <p>Some   <b>bold</b>\n text</p>\n  </body>\n</html>\n"),
+            "<!DOCTYPE html><html><head><title>A page</title><body> <p>Some <b>bold</b> text</p> "
+        );
+    }
+
+    #[test]
+    fn drops_comments() {
+        assert_eq!(
+            minified("<!DOCTYPE html><body><p>a<!-- note -->b</p></body>"),
+            "<!DOCTYPE html><html><head><body><p>ab"
+        );
+    }
+
+    #[test]
+    fn keeps_preformatted_text() {
+        assert_eq!(
+            minified(
+                "<!DOCTYPE html><body><pre>  a\n   b</pre><script>if (a < b)  {}</script></body>"
+            ),
+            "<!DOCTYPE html><html><head><body><pre>  a\n   b</pre><script>if (a < b)  {}</script>"
+        );
+    }
+
+    #[test]
+    fn leaves_out_optional_quotes() {
+        assert_eq!(
+            minified(
+                r#"<!DOCTYPE html><body><a href="/a" class="x y" title="">a &amp; b</a><img alt="&quot;"></body>"#
+            ),
+            r#"<!DOCTYPE html><html><head><body><a href=/a class="x y" title>a &amp; b</a><img alt="&quot;">"#
+        );
+    }
+
+    #[test]
+    fn leaves_out_optional_end_tags() {
+        assert_eq!(
+            minified("<!DOCTYPE html><body><ul><li>a</li><li>b</li></ul><p>c</p><div><p>d</p></div></body>"),
+            "<!DOCTYPE html><html><head><body><ul><li>a<li>b</ul><p>c<div><p>d</div>"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use crate::asset::{url, Manifest};
+use crate::i18n::Languages;
+use crate::images::Images;
+use crate::processor::{attribute, element, find_element};
+use crate::redirect::Redirects;
+use crate::report::PageStats;
+use crate::writer::Enqueuer;
+use markup5ever_rcdom::{Handle, NodeData, SerializableHandle};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// A rendered page waiting to be written
+pub struct Page {
+    pub handle: Handle,
+    pub source: PathBuf,
+    pub output: PathBuf,
+    /// Output path without language, shared by all translations of a page
+    pub key: PathBuf,
+    pub lang: Option<String>,
+    pub aliases: Vec<String>,
+    pub stats: PageStats,
+}
+
+/// Everything needed to write out rendered pages
+pub struct Pages<'a> {
+    pub writer: Enqueuer,
+    pub redirects: Redirects,
+    pub minify: bool,
+    pub assets: &'a Manifest,
+    pub images: &'a Images,
+    pub languages: &'a Languages,
+    pub rendered: Vec<Page>,
+    /// Stats of written pages by output path, for the build report
+    pub stats: HashMap<PathBuf, PageStats>,
+}
+
+impl Pages<'_> {
+    /// Writes all pages rendered so far. Pages are only written once everything is rendered, so
+    /// translations can link to each other.
+    pub fn write(&mut self) {
+        let mut translations = HashMap::<_, Vec<_>>::new();
+        for page in &self.rendered {
+            if let Some(lang) = &page.lang {
+                translations
+                    .entry(page.key.clone())
+                    .or_default()
+                    .push((lang.clone(), url(&page.output)));
+            }
+        }
+
+        let opts = html5ever::serialize::SerializeOpts {
+            create_missing_parent: true,
+            ..Default::default()
+        };
+        for page in std::mem::take(&mut self.rendered) {
+            if let Some(lang) = &page.lang {
+                set_lang(&page.handle, lang);
+            }
+            if let Some(links) = translations.get(&page.key).filter(|links| links.len() > 1) {
+                add_alternates(&page.handle, links);
+            }
+
+            self.images.rewrite(&page.handle, &page.output, self.assets);
+            self.assets.rewrite(&page.handle, &page.output);
+
+            let ser = if self.minify {
+                crate::minify::minify(&page.handle);
+                crate::minify::serialize(&page.handle)
+            } else {
+                let mut ser = Vec::new();
+                html5ever::serialize(
+                    &mut ser,
+                    &Into::<SerializableHandle>::into(page.handle),
+                    opts.clone(),
+                )
+                .unwrap();
+                String::from_utf8(ser).unwrap()
+            };
+
+            let page_url = url(&page.output);
+            for alias in page.aliases {
+                self.redirects.add(&alias, &page_url, page.source.clone());
+            }
+
+            self.stats.insert(page.output.clone(), page.stats);
+            self.writer.file(page.output, ser, page.source);
+        }
+    }
+}
+
+/// Sets the `lang` attribute of the html element, unless it already has one
+fn set_lang(handle: &Handle, lang: &str) {
+    if let Some(html) = find_element(handle, "html") {
+        if let NodeData::Element { attrs, .. } = &html.data {
+            let mut attrs = attrs.borrow_mut();
+            if !attrs.iter().any(|attr| attr.name.local == *"lang") {
+                attrs.push(attribute("lang", lang));
+            }
+        }
+    }
+}
+
+/// Links all translations of a page in its head
+fn add_alternates(handle: &Handle, links: &[(String, String)]) {
+    let head = match find_element(handle, "head") {
+        Some(head) => head,
+        None => return,
+    };
+    let mut children = head.children.borrow_mut();
+    for (lang, href) in links {
+        children.push(element(
+            "link",
+            vec![
+                attribute("rel", "alternate"),
+                attribute("hreflang", lang),
+                attribute("href", href),
+            ],
+            vec![],
+        ));
+    }
+}
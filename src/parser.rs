@@ -1,6 +1,12 @@
 use html5ever::tendril::TendrilSink;
 use html5ever::{local_name, namespace_url, ns, parse_fragment, ParseOpts, Parser, QualName};
-use markup5ever_rcdom::{Handle, RcDom};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use pulldown_cmark::{Event, Tag};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// Marks the `<template>` elements super tags in `<head>` are hidden in while parsing
+const HEAD_MARKER: &str = "data-super-head";
 
 pub fn parse_snippet(snippet: &str) -> Vec<Handle> {
     parse(make_sub_parser(), snippet)
@@ -15,7 +21,151 @@ pub fn parse_snippet(snippet: &str) -> Vec<Handle> {
 }
 
 pub fn parse_document(doc: &str) -> Handle {
-    parse(make_doc_parser(), doc)
+    let handle = parse(make_doc_parser(), &protect_head(doc));
+    restore_head(&handle);
+    handle
+}
+
+/// The html parser moves unknown elements in `<head>`, and everything after them, into the body.
+/// Super tags in the head are wrapped in `<template>` elements, which may appear there.
+fn protect_head(doc: &str) -> Cow<'_, str> {
+    let lower = doc.to_ascii_lowercase();
+    let head = lower.match_indices("<head").find(
+        |(i, _)| matches!(lower[i + 5..].chars().next(), Some(c) if c == '>' || c.is_whitespace()),
+    );
+    let mut pos = match head.and_then(|(i, _)| tag_end(doc, i)) {
+        Some(pos) => pos,
+        None => return Cow::Borrowed(doc),
+    };
+    let mut out = String::with_capacity(doc.len());
+    let mut copied = 0;
+    while let Some(i) = lower[pos..].find('<').map(|i| i + pos) {
+        let tag = &lower[i..];
+        if tag.starts_with("</head") {
+            break;
+        }
+        if tag.starts_with("<!--") {
+            pos = tag.find("-->").map_or(doc.len(), |end| i + end + 3);
+        } else if tag.starts_with("<super:") {
+            let end = element_end(doc, &lower, i);
+            out.push_str(&doc[copied..i]);
+            out.push_str(&format!("<template {}>", HEAD_MARKER));
+            out.push_str(&doc[i..end]);
+            out.push_str("</template>");
+            copied = end;
+            pos = end;
+        } else if is_tag(tag) {
+            pos = tag_end(doc, i).unwrap_or(doc.len());
+            // The content of these is text, it can't contain tags
+            let name = tag_name(tag);
+            if !tag.starts_with("</") && ["script", "style", "title", "noscript"].contains(&name) {
+                let close = format!("</{}", name);
+                pos = lower[pos..].find(&close).map_or(doc.len(), |end| pos + end);
+            }
+        } else {
+            pos = i + 1;
+        }
+    }
+    if copied == 0 {
+        return Cow::Borrowed(doc);
+    }
+    out.push_str(&doc[copied..]);
+    Cow::Owned(out)
+}
+
+/// End of the element whose start tag is at `start`, including nested elements of the same name.
+/// Only the start tag is taken when the element is never closed.
+fn element_end(doc: &str, lower: &str, start: usize) -> usize {
+    let open = match tag_end(doc, start) {
+        Some(open) => open,
+        None => return doc.len(),
+    };
+    if doc[..open].ends_with("/>") {
+        return open;
+    }
+    let name = tag_name(&lower[start..]);
+    let mut depth = 1;
+    let mut pos = open;
+    while let Some(i) = lower[pos..].find('<').map(|i| i + pos) {
+        let tag = &lower[i..];
+        if !is_tag(tag) {
+            pos = i + 1;
+            continue;
+        }
+        pos = match tag_end(doc, i) {
+            Some(end) => end,
+            None => break,
+        };
+        if tag_name(tag) != name {
+            continue;
+        }
+        if tag.starts_with("</") {
+            depth -= 1;
+            if depth == 0 {
+                return pos;
+            }
+        } else if !doc[..pos].ends_with("/>") {
+            depth += 1;
+        }
+    }
+    open
+}
+
+/// Position after the `>` closing the tag at `start`, `>` in quoted attribute values is skipped
+fn tag_end(doc: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, ch) in doc[start..].char_indices() {
+        match (quote, ch) {
+            (Some(q), ch) if ch == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(ch),
+            (None, '>') => return Some(start + i + 1),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Whether a `<` starts a tag rather than being text
+fn is_tag(tag: &str) -> bool {
+    let tag = tag.strip_prefix("</").unwrap_or(&tag[1..]);
+    tag.starts_with(|c: char| c.is_ascii_alphabetic() || c == '!')
+}
+
+/// Name of the tag at the start of `tag`, `<a href="">` and `</a>` are both `a`
+fn tag_name(tag: &str) -> &str {
+    let tag = tag.trim_start_matches('<').trim_start_matches('/');
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+/// Replaces the templates added by `protect_head` with their content
+fn restore_head(handle: &Handle) {
+    let children = handle.children.take();
+    let mut restored = Vec::with_capacity(children.len());
+    for child in children {
+        if let NodeData::Element {
+            name,
+            attrs,
+            template_contents: Some(contents),
+            ..
+        } = &child.data
+        {
+            let marked = attrs
+                .borrow()
+                .iter()
+                .any(|attr| attr.name.local == *HEAD_MARKER);
+            if name.local == *"template" && marked {
+                restored.append(&mut contents.children.take());
+                continue;
+            }
+        }
+        restore_head(&child);
+        restored.push(child);
+    }
+    *handle.children.borrow_mut() = restored;
 }
 
 fn parse(parser: Parser<RcDom>, src: &str) -> Handle {
@@ -40,15 +190,256 @@ fn make_sub_parser() -> Parser<RcDom> {
 }
 
 // FIXME: This is a hack im not proud of, it should be improved int he future
-pub fn parse_markdown(md: &str) -> Vec<Handle> {
+/// `text` is applied to all text outside of code
+pub fn parse_markdown(md: &str, text: impl FnMut(&str) -> String) -> Vec<Handle> {
+    parse_snippet(&render_markdown(md, text))
+}
+
+pub fn render_markdown(md: &str, mut text: impl FnMut(&str) -> String) -> String {
     let mut opt = pulldown_cmark::Options::empty();
     opt.insert(pulldown_cmark::Options::ENABLE_TABLES);
     opt.insert(pulldown_cmark::Options::ENABLE_FOOTNOTES);
     opt.insert(pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
 
-    let parser = pulldown_cmark::Parser::new_ext(md, opt);
+    let closing = closing_shortcodes(md);
+    let mut events = vec![];
+    let mut code = false;
+    // Text is split into several events at characters that could be markup
+    let mut pending = String::new();
+    for (event, range) in pulldown_cmark::Parser::new_ext(md, opt).into_offset_iter() {
+        match event {
+            // The backslash of an escape is dropped and the escaped character starts a new text
+            // event, it's literal text that can't start a placeholder or shortcode (`\{{`)
+            Event::Text(str)
+                if !code && range.start > 0 && md.as_bytes()[range.start - 1] == b'\\' =>
+            {
+                shortcodes(
+                    &std::mem::take(&mut pending),
+                    &closing,
+                    &mut events,
+                    &mut text,
+                );
+                let escaped = str.chars().next().map_or(0, char::len_utf8);
+                events.push(Event::Text(str[..escaped].to_string().into()));
+                pending.push_str(&str[escaped..]);
+                continue;
+            }
+            Event::Text(str) if !code => {
+                pending.push_str(&str);
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_)) => code = true,
+            Event::End(Tag::CodeBlock(_)) => code = false,
+            _ => (),
+        }
+        shortcodes(
+            &std::mem::take(&mut pending),
+            &closing,
+            &mut events,
+            &mut text,
+        );
+        events.push(event);
+    }
+    shortcodes(&pending, &closing, &mut events, &mut text);
+
+    // A shortcode on its own is a block, not part of a paragraph
+    let mut i = 0;
+    while i < events.len() {
+        if let Event::Start(Tag::Paragraph) = events[i] {
+            let end = i + events[i..]
+                .iter()
+                .position(|event| matches!(event, Event::End(Tag::Paragraph)))
+                .unwrap();
+            if is_block_shortcode(&events[i + 1..end]) {
+                events.remove(end);
+                events.remove(i);
+                continue;
+            }
+        }
+        i += 1;
+    }
     let mut snippet = String::new();
-    pulldown_cmark::html::push_html(&mut snippet, parser);
+    pulldown_cmark::html::push_html(&mut snippet, events.into_iter());
+    snippet
+}
+
+/// Expands `{{< name key="value" >}}` shortcodes in markdown text into `super:component` elements,
+/// `text` is applied to the text around them.
+/// A shortcode wraps everything up to a matching `{{< /name >}}`, if the page contains one,
+/// `{{< name />}}` never does.
+fn shortcodes<'a>(
+    src: &str,
+    closing: &HashSet<String>,
+    events: &mut Vec<Event<'a>>,
+    text: &mut impl FnMut(&str) -> String,
+) {
+    let mut rest = src;
+    while let Some(start) = rest.find("{{<") {
+        let end = match rest[start..].find(">}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        if start > 0 {
+            events.push(Event::Text(text(&rest[..start]).into()));
+        }
+        let inner = rest[start + 3..end].trim();
+        let html = match inner.strip_prefix('/') {
+            Some(_) => "</super:component>".to_string(),
+            None => {
+                let (inner, standalone) = match inner.strip_suffix('/') {
+                    Some(inner) => (inner.trim_end(), true),
+                    None => (inner, false),
+                };
+                let (name, params) =
+                    inner.split_at(inner.find(char::is_whitespace).unwrap_or(inner.len()));
+                let mut html = format!("<super:component name=\"{}\"", escape_attr(name));
+                let params = match shortcode_params(params) {
+                    Ok(params) => params,
+                    Err(err) => {
+                        crate::fatal!("Invalid shortcode; shortcode={}; error={}", inner, err)
+                    }
+                };
+                for (key, value) in params {
+                    html.push_str(&format!(" {}=\"{}\"", key, escape_attr(value)));
+                }
+                html.push('>');
+                if standalone || !closing.contains(name) {
+                    html.push_str("</super:component>");
+                }
+                html
+            }
+        };
+        events.push(Event::Html(html.into()));
+        rest = &rest[end + 3..];
+    }
+    if !rest.is_empty() {
+        events.push(Event::Text(text(rest).into()));
+    }
+}
+
+/// Whether the content of a paragraph is a single shortcode, one spanning the whole paragraph or
+/// the start or end of one spanning several paragraphs
+fn is_block_shortcode(events: &[Event]) -> bool {
+    let html = |event: &Event| match event {
+        Event::Html(html) if html.starts_with("<super:component") => Some(1),
+        Event::Html(html) if html.starts_with("</super:component") => Some(-1),
+        _ => None,
+    };
+    match events {
+        [only] => html(only).is_some(),
+        [first, .., last] if html(first) == Some(1) && html(last) == Some(-1) => {
+            // The first shortcode may only be closed by the last event
+            let mut depth = 0;
+            for event in &events[..events.len() - 1] {
+                depth += match event {
+                    // Shortcodes used on their own are opened and closed in the same event
+                    Event::Html(tag)
+                        if tag.ends_with("</super:component>") && !tag.starts_with("</") =>
+                    {
+                        0
+                    }
+                    event => html(event).unwrap_or(0),
+                };
+                if depth == 0 {
+                    return false;
+                }
+            }
+            depth == 1
+        }
+        _ => false,
+    }
+}
+
+/// `key="value"` or `key=value` pairs, a key without value is set to an empty value.
+/// Keys become attribute names, so only letters, digits, `_` and `-` are allowed in them.
+fn shortcode_params(mut params: &str) -> Result<Vec<(&str, &str)>, String> {
+    let mut parsed = vec![];
+    loop {
+        params = params.trim_start();
+        if params.is_empty() {
+            return Ok(parsed);
+        }
+        let key_end = params
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(params.len());
+        let key = &params[..key_end];
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("Invalid parameter name {:?}", key));
+        }
+        params = &params[key_end..];
+        let value = match params.strip_prefix('=') {
+            Some(value) => match value.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    params = quoted.get(end + 1..).unwrap_or("");
+                    &quoted[..end]
+                }
+                None => {
+                    let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                    params = &value[end..];
+                    &value[..end]
+                }
+            },
+            None => "",
+        };
+        parsed.push((key, value));
+    }
+}
+
+/// Names of all shortcodes closed somewhere in `md`
+fn closing_shortcodes(md: &str) -> HashSet<String> {
+    md.split("{{<")
+        .skip(1)
+        .filter_map(|code| code.split(">}}").next()?.trim().strip_prefix('/'))
+        .map(|name| name.trim().to_string())
+        .collect()
+}
+
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    parse_snippet(&snippet)
+    #[test]
+    fn shortcode_params_are_parsed() {
+        assert_eq!(
+            shortcode_params(r#" id="dQw4w9WgXcQ" start=10 autoplay title="a b""#),
+            Ok(vec![
+                ("id", "dQw4w9WgXcQ"),
+                ("start", "10"),
+                ("autoplay", ""),
+                ("title", "a b"),
+            ])
+        );
+        assert_eq!(shortcode_params("  "), Ok(vec![]));
+        assert_eq!(shortcode_params("data-x_1=a"), Ok(vec![("data-x_1", "a")]));
+    }
+
+    #[test]
+    fn shortcode_params_reject_invalid_names() {
+        assert!(shortcode_params(r#"x"onload=1"#).is_err());
+        assert!(shortcode_params(r#"a="1" b>c=2"#).is_err());
+        assert!(shortcode_params("=a").is_err());
+    }
+
+    #[test]
+    fn head_super_tags_are_protected() {
+        let doc = r#"<html><head><title>a < b</title><super:include src="a.html" title="a > b"><super:include src="b.html"></super:include></super:include><meta></head></html>"#;
+        assert_eq!(
+            protect_head(doc),
+            r#"<html><head><title>a < b</title><template data-super-head><super:include src="a.html" title="a > b"><super:include src="b.html"></super:include></super:include></template><meta></head></html>"#
+        );
+        let untouched = "<html><head><script>if (a<super:x) {}</script></head></html>";
+        assert!(matches!(protect_head(untouched), Cow::Borrowed(_)));
+    }
 }
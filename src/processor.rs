@@ -1,54 +1,194 @@
+use crate::asset::Manifest;
 use crate::config::VarStack;
+use crate::expr::Value;
 use crate::fatal;
-use crate::writer::Enqueuer;
+use crate::i18n::Languages;
+use crate::images::Images;
 use html5ever::tendril::StrTendril;
-use html5ever::{local_name, namespace_url, ns, Attribute, QualName};
+use html5ever::{namespace_url, ns, Attribute, QualName};
 use markup5ever_rcdom::{Handle, Node, NodeData};
+use path_clean::PathClean;
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 pub trait Processor {
-    fn one(&mut self, path: PathBuf) -> Handle {
-        self.process(&path)
-    }
+    fn process(&mut self, path: &Path) -> Handle;
 
-    fn many(&mut self, path_bufs: Vec<PathBuf>) -> Vec<Handle> {
-        path_bufs
-            .into_iter()
-            .map(|path| self.process(&path))
-            .collect()
+    /// Front matter of an already processed file
+    fn front_matter(&self, _path: &Path) -> Option<&HashMap<String, String>> {
+        None
     }
 
-    fn process(&mut self, path: &Path) -> Handle;
+    /// Files read while processing `path` (templates, includes), besides `path` itself
+    fn dependencies(&mut self, _path: &Path) -> Vec<PathBuf> {
+        vec![]
+    }
 }
 
 #[derive(Clone)]
 pub struct ProcessorConfig<'a> {
-    pub out_path: &'a Path,
     pub vars: VarStack,
-    pub writer: Enqueuer,
+    pub assets: &'a Manifest,
+    pub images: &'a Images,
+    pub languages: &'a Languages,
+    /// Files used by `super:component`, by name
+    pub components: &'a HashMap<String, PathBuf>,
 }
 
 pub struct HtmlProcessor<'a> {
     pub cfg: ProcessorConfig<'a>,
     pub stack: Vec<String>,
-    pub content: Vec<Vec<Handle>>,
+    /// Children of the `super:wrap` elements and includes currently being processed, innermost last
+    pub content: Vec<Content>,
+    /// What `super:parent` is replaced with while a slot is evaluated
+    pub parent: Option<Vec<Handle>>,
+    /// Every file processed since the dependencies were last taken
+    pub read: Vec<PathBuf>,
+}
+
+/// Options of `super:include` that aren't passed on as variables
+#[derive(Default)]
+struct IncludeOptions {
+    /// First and last line to include, starting at 1
+    lines: Option<(usize, usize)>,
+    /// Place the file as code in this language. Taken before attributes become variables, so it
+    /// never hides the `lang` of the page
+    lang: Option<String>,
+    /// Render the file as markdown, whatever its extension
+    markdown: bool,
+}
+
+impl IncludeOptions {
+    fn take(attrs: &mut Vec<Attribute>, path: &str) -> Self {
+        let lines = take_attr(attrs, "lines").map(|lines| {
+            // An empty range is more likely a mistake than a way to include everything
+            if lines.trim().trim_matches('-').trim().is_empty() {
+                fatal!("Empty line range; lines={}; path={}", lines, path);
+            }
+            let (first, last) = lines.split_once('-').unwrap_or((&lines, &lines));
+            let first = match first.trim() {
+                "" => Ok(1),
+                first => first.parse(),
+            };
+            let last = match last.trim() {
+                "" => Ok(usize::MAX),
+                last => last.parse(),
+            };
+            match (first, last) {
+                (Ok(first), Ok(last)) if first >= 1 && first <= last => (first, last),
+                _ => fatal!("Invalid line range; lines={}; path={}", lines, path),
+            }
+        });
+        Self {
+            lines,
+            lang: take_attr(attrs, "lang"),
+            markdown: take_attr(attrs, "markdown").is_some_and(|markdown| markdown == "true"),
+        }
+    }
+}
+
+/// Children of a `super:wrap` or include, waiting to be placed by `super:content` in the template
+pub struct Content {
+    /// Everything outside of a `super:slot`
+    main: Option<Vec<Handle>>,
+    /// Children of `super:slot` elements by name
+    slots: HashMap<String, Vec<Handle>>,
+    /// Length of the stack where the children were written, they are evaluated there
+    depth: usize,
+    /// Variables where the children were written
+    vars: VarStack,
+    /// Children of an include, which doesn't see the content of the page it is part of
+    include: bool,
+}
+
+impl Content {
+    fn new(
+        children: Vec<Handle>,
+        path: &Path,
+        depth: usize,
+        vars: VarStack,
+        include: bool,
+    ) -> Self {
+        let mut main = Vec::with_capacity(children.len());
+        let mut slots = HashMap::<String, Vec<Handle>>::new();
+        for child in children {
+            if element_name(&child) != "super:slot" {
+                main.push(child);
+                continue;
+            }
+            let name = match &child.data {
+                NodeData::Element { attrs, .. } => take_attr(&mut attrs.borrow_mut(), "name"),
+                _ => None,
+            };
+            match name {
+                Some(name) => slots
+                    .entry(name)
+                    .or_default()
+                    .append(&mut child.children.take()),
+                None => fatal!(
+                    "Invalid slot element. No name; path={}",
+                    path.to_string_lossy()
+                ),
+            }
+        }
+        // Without content the template falls back to its default content
+        let empty = main.iter().all(is_whitespace);
+        Self {
+            main: if empty { None } else { Some(main) },
+            slots,
+            depth,
+            vars,
+            include,
+        }
+    }
+
+    fn warn_unused(&self, path: &Path) {
+        for name in self.slots.keys() {
+            tracing::warn!(
+                "Slot not used by any template; name={}; path={}",
+                name,
+                path.to_string_lossy()
+            );
+        }
+    }
 }
 
 pub struct MarkdownProcessor<'a> {
     pub cfg: ProcessorConfig<'a>,
     pub template: String,
+    pub front_matter: HashMap<PathBuf, HashMap<String, String>>,
+    pub read: Vec<PathBuf>,
+    /// Files being processed when this one was included
+    pub stack: Vec<String>,
+    /// Replace `{{VAR}}` in the text, can also be enabled with `; interpolate: true`
+    pub interpolate: bool,
 }
 
 impl<'a> Processor for HtmlProcessor<'a> {
     fn process(&mut self, path: &Path) -> Handle {
-        let handle = Self::read_handle(path);
-        self.markdown(path, handle) // This method actually does the same as would be normally done here
+        self.process_source(path, &read_file(path))
+    }
+
+    fn dependencies(&mut self, path: &Path) -> Vec<PathBuf> {
+        dependencies(std::mem::take(&mut self.read), path)
     }
 }
 
 impl<'a> HtmlProcessor<'a> {
+    /// Processes `src` as if it was the content of `path`
+    pub fn process_source(&mut self, path: &Path, src: &str) -> Handle {
+        let vars = self.cfg.vars.clone();
+        if let Some(lang) = self.cfg.languages.of_path(path) {
+            self.cfg.vars = vars.combine(self.cfg.languages.vars(lang));
+        }
+        self.read.push(path.to_path_buf());
+        let handle = Self::parse_source(src);
+        let handle = self.markdown(path, handle); // This method actually does the same as would be normally done here
+        self.cfg.vars = vars;
+        handle
+    }
+
     /// Post processes markdown
     pub fn markdown(&mut self, path: &Path, handle: Handle) -> Handle {
         self.stack.push(path.to_string_lossy().to_string());
@@ -57,10 +197,17 @@ impl<'a> HtmlProcessor<'a> {
         inner
     }
 
-    fn process_inner(&mut self, path: &Path, mut handle: Handle) -> Handle {
+    fn process_inner(&mut self, path: &Path, handle: Handle) -> Handle {
         if let NodeData::Document = &handle.data {
-            if handle.children.borrow().len() == 1 {
-                let children = handle.children.borrow();
+            // Whitespace around the root element, like a trailing newline, doesn't count
+            let children = handle
+                .children
+                .borrow()
+                .iter()
+                .filter(|child| !is_whitespace(child))
+                .cloned()
+                .collect::<Vec<_>>();
+            if children.len() == 1 {
                 let handle = children.first().unwrap();
                 if let NodeData::Element { name, attrs, .. } = &handle.data {
                     if name.local == *"super:wrap" {
@@ -74,23 +221,39 @@ impl<'a> HtmlProcessor<'a> {
                                 path.to_string_lossy()
                             );
                         }
-                        let src = src.unwrap();
-                        self.content.push(handle.children.take());
-                        return self.process(Path::new(&src.value.to_string()));
+                        let src = PathBuf::from(src.unwrap().value.to_string());
+                        self.check_cycle(&src);
+                        self.content.push(Content::new(
+                            handle.children.take(),
+                            path,
+                            self.stack.len(),
+                            self.cfg.vars.clone(),
+                            false,
+                        ));
+                        let handle = self.process(&src);
+                        self.content.pop().unwrap().warn_unused(path);
+                        return handle;
                     }
                 }
             }
         }
-        self.traverse(handle.clone(), &mut handle)
+        self.traverse(handle)
     }
 
-    fn traverse(&mut self, handle: Handle, root: &mut Handle) -> Handle {
-        let mut redo = false;
+    fn traverse(&mut self, handle: Handle) -> Handle {
         let new = Vec::with_capacity(handle.children.borrow().len());
-        let children = handle.children.replace(new);
-        for el in children.into_iter() {
+        // Placed content and includes are processed before they are inserted
+        for el in handle.children.replace(new) {
             if let NodeData::Element { name, attrs, .. } = &el.data {
                 if let Some(name) = name.local.to_string().strip_prefix("super:") {
+                    // Includes get their variables, and their src, from these
+                    let path = self.stack.last().unwrap();
+                    for attr in attrs.borrow_mut().iter_mut() {
+                        if attr.value.contains("{{") {
+                            attr.value =
+                                StrTendril::from(interpolate(&attr.value, &self.cfg.vars, path));
+                        }
+                    }
                     match name {
                         "wrap" => {
                             tracing::error!(
@@ -100,100 +263,363 @@ impl<'a> HtmlProcessor<'a> {
                             continue;
                         }
                         "content" => {
-                            let children = self.content.pop();
-                            if children.is_none() {
-                                tracing::error!("Content tag found, but no content available");
-                                continue;
-                            }
-                            let mut ch_mut = handle.children.borrow_mut();
-                            for child in children.unwrap() {
-                                ch_mut.push(child);
-                            }
-                            redo = true;
+                            let slot = take_attr(&mut attrs.borrow_mut(), "name");
+                            let placed = match slot {
+                                Some(slot) => self.block(&slot, el.children.take()),
+                                // The innermost page that hasn't been placed yet
+                                None => match self.take_main() {
+                                    Some((index, main)) => self.place(main, Some(index), None),
+                                    None if el.children.borrow().is_empty() => {
+                                        tracing::error!(
+                                            "Content tag found, but no content available"
+                                        );
+                                        continue;
+                                    }
+                                    // Otherwise the children of the tag are the default content
+                                    None => self.place(el.children.take(), None, None),
+                                },
+                            };
+                            handle.children.borrow_mut().extend(placed);
                             continue;
                         }
-                        "include" => {
-                            let src = attrs
-                                .take()
-                                .into_iter()
-                                .find(|attr| attr.name.local == *"src");
-                            if src.is_none() {
-                                fatal!(
-                                    "Invalid warp element. No src; path={}",
-                                    self.stack.last().unwrap()
-                                );
-                            }
-                            let src = src.unwrap();
-
-                            let new_handle = if src.value.ends_with(".html") {
-                                self.process(Path::new(&src.value.to_string()))
-                            } else if src.value.ends_with(".md") {
-                                MarkdownProcessor {
-                                    cfg: self.cfg.clone(),
-                                    template: "".to_string(),
+                        "include" | "component" => {
+                            let mut attrs = attrs.take();
+                            let mut options = IncludeOptions::default();
+                            let src = if name == "include" {
+                                options =
+                                    IncludeOptions::take(&mut attrs, self.stack.last().unwrap());
+                                match take_attr(&mut attrs, "src") {
+                                    Some(src) => src,
+                                    None => fatal!(
+                                        "Invalid include element. No src; path={}",
+                                        self.stack.last().unwrap()
+                                    ),
                                 }
-                                .process(Path::new(&src.value.to_string()))
                             } else {
-                                Handle::new(Node {
-                                    parent: Cell::new(None),
-                                    children: RefCell::new(vec![]),
-                                    data: NodeData::Text {
-                                        contents: RefCell::new(StrTendril::from(
-                                            match std::fs::read_to_string(Path::new(
-                                                &src.value.to_string(),
-                                            )) {
-                                                Ok(str) => str,
-                                                Err(err) => fatal!(
-                                                    "Unable to read file; path={}; error={}",
-                                                    src.value,
-                                                    err
-                                                ),
-                                            },
-                                        )),
-                                    },
-                                })
+                                let component = match take_attr(&mut attrs, "name") {
+                                    Some(component) => component,
+                                    None => fatal!(
+                                        "Invalid component element. No name; path={}",
+                                        self.stack.last().unwrap()
+                                    ),
+                                };
+                                match self.cfg.components.get(&component.to_lowercase()) {
+                                    Some(src) => src.to_string_lossy().to_string(),
+                                    None => fatal!(
+                                        "Unknown component; name={}; path={}",
+                                        component,
+                                        self.stack.last().unwrap()
+                                    ),
+                                }
                             };
+                            let new_handle = self.include(&src, attrs, el.children.take(), options);
 
-                            if let NodeData::Document = &new_handle.data {
-                                let mut children = handle.children.borrow_mut();
-                                for child in new_handle.children.take() {
-                                    children.push(child);
+                            insert(&handle, new_handle);
+                            continue;
+                        }
+                        "parent" => {
+                            match &self.parent {
+                                Some(parent) => {
+                                    handle.children.borrow_mut().extend(parent.iter().cloned())
                                 }
-                            } else {
-                                handle.children.borrow_mut().push(new_handle.clone());
+                                None => tracing::error!(
+                                    "Found super:parent outside of a slot; path={}",
+                                    self.stack.last().unwrap()
+                                ),
                             }
-                            redo = true;
                             continue;
                         }
-                        tag => tracing::warn!(
-                            "Unknown (or unimplemented) super tag found; tag={}",
-                            tag
-                        ),
+                        "asset" => {
+                            let mut attrs = attrs.take();
+                            let src = match take_attr(&mut attrs, "src") {
+                                Some(src) => src,
+                                None => fatal!(
+                                    "Invalid asset element. No src; path={}",
+                                    self.stack.last().unwrap()
+                                ),
+                            };
+                            let url = match self.cfg.assets.get(&src) {
+                                Some(url) => url,
+                                None => fatal!(
+                                    "Unknown asset; src={}; path={}",
+                                    src,
+                                    self.stack.last().unwrap()
+                                ),
+                            };
+                            let ext = Path::new(url).extension().and_then(|ext| ext.to_str());
+                            let (tag, url_attr) = match ext {
+                                Some("css") => {
+                                    if !attrs.iter().any(|attr| attr.name.local == *"rel") {
+                                        attrs.push(attribute("rel", "stylesheet"));
+                                    }
+                                    ("link", "href")
+                                }
+                                Some("js") => ("script", "src"),
+                                Some("png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "avif") => {
+                                    ("img", "src")
+                                }
+                                _ => ("a", "href"),
+                            };
+                            attrs.push(attribute(url_attr, url));
+                            handle.children.borrow_mut().push(element(
+                                tag,
+                                attrs,
+                                el.children.take(),
+                            ));
+                            continue;
+                        }
+                        "image" => {
+                            let mut attrs = attrs.take();
+                            let src = match take_attr(&mut attrs, "src") {
+                                Some(src) => src,
+                                None => fatal!(
+                                    "Invalid image element. No src; path={}",
+                                    self.stack.last().unwrap()
+                                ),
+                            };
+                            let img = self.cfg.images.element(Path::new(&src), attrs);
+                            handle.children.borrow_mut().push(img);
+                            continue;
+                        }
+                        tag => match self.cfg.components.get(tag) {
+                            Some(src) => {
+                                let src = src.to_string_lossy().to_string();
+                                let new_handle = self.include(
+                                    &src,
+                                    attrs.take(),
+                                    el.children.take(),
+                                    IncludeOptions::default(),
+                                );
+                                insert(&handle, new_handle);
+                                continue;
+                            }
+                            None => tracing::warn!(
+                                "Unknown (or unimplemented) super tag found; tag={}",
+                                tag
+                            ),
+                        },
                     }
                 }
             }
+            if !self.attributes(&el) {
+                continue;
+            }
             handle.children.borrow_mut().push(el.clone());
-            self.traverse(el, root);
+            self.traverse(el);
         }
-        // Reevaluate changed elements
-        if redo {
-            self.traverse(handle, root)
-        } else {
+        handle
+    }
+
+    /// Processes an included file. Its attributes are available to it as variables and its
+    /// children are placed by `super:content`, like the content of a `super:wrap`.
+    fn include(
+        &mut self,
+        src: &str,
+        attrs: Vec<Attribute>,
+        children: Vec<Handle>,
+        options: IncludeOptions,
+    ) -> Handle {
+        let path = Path::new(src);
+        self.check_cycle(path);
+        // Children are evaluated where they were written, with the variables of the page
+        self.content.push(Content::new(
+            children,
+            path,
+            self.stack.len(),
+            self.cfg.vars.clone(),
+            true,
+        ));
+        let parent = self.parent.take();
+        let params = attrs
+            .into_iter()
+            .map(|attr| (attr.name.local.to_string(), attr.value.to_string()))
+            .collect::<HashMap<_, _>>();
+        let vars = self.cfg.vars.clone();
+        if !params.is_empty() {
+            self.cfg.vars = vars.combine(params);
+        }
+        let source = match options.lines {
+            Some(lines) => slice_lines(&read_file(path), lines, path),
+            None => read_file(path),
+        };
+        let handle = if let Some(lang) = &options.lang {
+            self.read.push(path.to_path_buf());
+            let class = attribute("class", &format!("language-{}", lang));
+            let code = element("code", vec![class], vec![text(&source)]);
+            element("pre", vec![], vec![code])
+        } else if options.markdown || src.ends_with(".md") {
+            let mut md = MarkdownProcessor {
+                cfg: self.cfg.clone(),
+                template: "".to_string(),
+                front_matter: HashMap::new(),
+                read: vec![],
+                stack: self.stack.clone(),
+                interpolate: false,
+            };
+            let handle = md.process_source(path, &source);
+            self.read.push(path.to_path_buf());
+            self.read.append(&mut md.read);
             handle
+        } else if src.ends_with(".html") {
+            self.process_source(path, &source)
+        } else {
+            self.read.push(path.to_path_buf());
+            text(&source)
+        };
+
+        self.content.pop().unwrap().warn_unused(path);
+        self.parent = parent;
+        self.cfg.vars = vars;
+        handle
+    }
+
+    /// Index of the first content visible here, includes only see their own children
+    fn visible(&self) -> usize {
+        self.content
+            .iter()
+            .rposition(|content| content.include)
+            .unwrap_or(0)
+    }
+
+    /// Takes the innermost main content that hasn't been placed yet, with the index of its content
+    fn take_main(&mut self) -> Option<(usize, Vec<Handle>)> {
+        let start = self.visible();
+        self.content[start..]
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .find_map(|(i, content)| content.main.take().map(|main| (start + i, main)))
+    }
+
+    /// Evaluates placed nodes. Nodes of the content at `index` are evaluated where they were
+    /// written, with its variables and stack and only the content outside of it visible, so
+    /// nesting a component in itself works and children see the variables of their page.
+    /// `parent` is placed by `super:parent`.
+    fn place(
+        &mut self,
+        nodes: Vec<Handle>,
+        index: Option<usize>,
+        parent: Option<Vec<Handle>>,
+    ) -> Vec<Handle> {
+        let parent = std::mem::replace(&mut self.parent, parent);
+        let outer = index.map(|index| {
+            let vars = self.content[index].vars.clone();
+            let depth = self.content[index].depth;
+            (
+                self.content.split_off(index),
+                self.stack.split_off(depth),
+                std::mem::replace(&mut self.cfg.vars, vars),
+            )
+        });
+        let placed = self
+            .traverse(element("super:placed", vec![], nodes))
+            .children
+            .take();
+        if let Some((mut content, mut stack, vars)) = outer {
+            self.content.append(&mut content);
+            self.stack.append(&mut stack);
+            self.cfg.vars = vars;
+        }
+        self.parent = parent;
+        placed
+    }
+
+    /// Resolves a named block. The definition of the outermost page wins, a `super:parent` in it
+    /// is replaced by the definition it overrides, down to the default content of the template.
+    fn block(&mut self, name: &str, default: Vec<Handle>) -> Vec<Handle> {
+        let start = self.visible();
+        let definitions = self.content[start..]
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, content)| content.slots.remove(name).map(|block| (start + i, block)))
+            .collect::<Vec<_>>();
+        // Every definition is evaluated where it was written, starting with the innermost
+        let mut placed = self.place(default, None, None);
+        for (index, block) in definitions.into_iter().rev() {
+            placed = self.place(block, Some(index), Some(placed));
         }
+        placed
     }
 
-    fn read_handle(path: &Path) -> Handle {
-        let read = read_file(path);
+    /// Stops templates or includes that (indirectly) use themselves
+    fn check_cycle(&self, src: &Path) {
+        // `a/../b.html` and `./b.html` have to match `b.html`
+        let clean = src.to_path_buf().clean();
+        if self
+            .stack
+            .iter()
+            .any(|path| PathBuf::from(path).clean() == clean)
+        {
+            fatal!(
+                "Template cycle found; chain={} -> {}",
+                self.stack.join(" -> "),
+                src.to_string_lossy()
+            );
+        }
+    }
+
+    /// Evaluates `super:` attributes of an element and placeholders in its attribute values.
+    /// Returns false if the element is removed by `super:if`.
+    fn attributes(&self, el: &Handle) -> bool {
+        let attrs = match &el.data {
+            NodeData::Element { attrs, .. } => attrs,
+            _ => return true,
+        };
+        let path = self.stack.last().unwrap();
+        let mut attrs = attrs.borrow_mut();
+        if let Some(condition) = take_attr(&mut attrs, "super:if") {
+            if !eval(&condition, &self.cfg.vars, path).truthy() {
+                return false;
+            }
+        }
+        let content = take_attr(&mut attrs, "super:content");
+        let html = take_attr(&mut attrs, "super:html");
+        if content.is_some() && html.is_some() {
+            fatal!(
+                "Element can't have both super:content and super:html; path={}",
+                path
+            );
+        }
+        // Text is escaped when written, only `super:html` places markup
+        if let Some(expr) = content {
+            let value = eval_value(&expr, &self.cfg.vars, path);
+            *el.children.borrow_mut() = vec![text(&value.to_string())];
+        }
+        if let Some(expr) = html {
+            let value = eval_value(&expr, &self.cfg.vars, path);
+            *el.children.borrow_mut() = crate::parser::parse_snippet(&value.to_string());
+        }
+        let mut set = vec![];
+        attrs.retain_mut(|attr| match attr.name.local.strip_prefix("super:attr-") {
+            Some(name) => {
+                let value = eval_value(&attr.value, &self.cfg.vars, path);
+                set.push((name.to_string(), value.to_string()));
+                false
+            }
+            None => {
+                if attr.value.contains("{{") {
+                    attr.value = StrTendril::from(interpolate(&attr.value, &self.cfg.vars, path));
+                }
+                true
+            }
+        });
+        for (name, value) in set {
+            take_attr(&mut attrs, &name);
+            attrs.push(attribute(&name, &value));
+        }
+        true
+    }
+
+    fn parse_source(read: &str) -> Handle {
         if read.trim_start().starts_with("<!DOCTYPE") {
-            crate::parser::parse_document(&read)
+            crate::parser::parse_document(read)
         } else {
             let node = Node {
                 data: NodeData::Document,
                 parent: Default::default(),
                 children: Default::default(),
             };
-            *node.children.borrow_mut() = crate::parser::parse_snippet(&read);
+            *node.children.borrow_mut() = crate::parser::parse_snippet(read);
             Handle::new(node)
         }
     }
@@ -201,7 +627,21 @@ impl<'a> HtmlProcessor<'a> {
 
 impl<'a> Processor for MarkdownProcessor<'a> {
     fn process(&mut self, path: &Path) -> Handle {
-        let src = read_file(path);
+        self.process_source(path, &read_file(path))
+    }
+
+    fn front_matter(&self, path: &Path) -> Option<&HashMap<String, String>> {
+        self.front_matter.get(path)
+    }
+
+    fn dependencies(&mut self, path: &Path) -> Vec<PathBuf> {
+        dependencies(std::mem::take(&mut self.read), path)
+    }
+}
+
+impl<'a> MarkdownProcessor<'a> {
+    /// Processes `src` as if it was the content of `path`
+    pub fn process_source(&mut self, path: &Path, src: &str) -> Handle {
         let mut new_src = String::with_capacity(src.len());
         let mut vars = HashMap::new();
         for line in src.lines() {
@@ -210,8 +650,8 @@ impl<'a> Processor for MarkdownProcessor<'a> {
                 let splitter = pre_trimmed.find(':');
                 if let Some(splitter) = splitter {
                     vars.insert(
-                        (&pre_trimmed[1..splitter]).trim().to_string(),
-                        (&pre_trimmed[splitter + 1..]).trim().to_string(),
+                        pre_trimmed[1..splitter].trim().to_string(),
+                        pre_trimmed[splitter + 1..].trim().to_string(),
                     );
                 }
             } else {
@@ -219,33 +659,30 @@ impl<'a> Processor for MarkdownProcessor<'a> {
                 new_src.push('\n');
             }
         }
-        let new_vars = self.cfg.vars.combine(vars);
+        let mut new_vars = self.cfg.vars.clone();
+        if let Some(lang) = self.cfg.languages.detect(path, Some(&vars)) {
+            new_vars = new_vars.combine(self.cfg.languages.vars(lang));
+        }
+        let new_vars = new_vars.combine(vars.clone());
         let mut new_cfg = self.cfg.clone();
         new_cfg.vars = new_vars;
-        let markdown = crate::parser::parse_markdown(&new_src);
+        let interpolate_text =
+            self.interpolate || vars.get("interpolate").map(String::as_str) == Some("true");
+        let markdown = if interpolate_text {
+            let path = path.to_string_lossy();
+            crate::parser::parse_markdown(&new_src, |text| interpolate(text, &new_cfg.vars, &path))
+        } else {
+            crate::parser::parse_markdown(&new_src, str::to_string)
+        };
+
+        self.front_matter.insert(path.to_path_buf(), vars);
 
         let wrap = if !self.template.is_empty() {
-            vec![Handle::new(Node {
-                parent: Cell::new(None),
-                children: RefCell::new(markdown),
-                data: NodeData::Element {
-                    name: QualName {
-                        prefix: None,
-                        ns: ns!(html),
-                        local: string_cache::Atom::from("super:wrap"),
-                    },
-                    attrs: RefCell::new(vec![Attribute {
-                        name: QualName {
-                            prefix: None,
-                            ns: ns!(),
-                            local: local_name!("src"),
-                        },
-                        value: StrTendril::from(self.template.clone()),
-                    }]),
-                    template_contents: None,
-                    mathml_annotation_xml_integration_point: false,
-                },
-            })]
+            vec![element(
+                "super:wrap",
+                vec![attribute("src", &self.template)],
+                markdown,
+            )]
         } else {
             markdown
         };
@@ -254,13 +691,216 @@ impl<'a> Processor for MarkdownProcessor<'a> {
             children: RefCell::new(wrap),
             data: NodeData::Document,
         });
-        HtmlProcessor {
-            cfg: self.cfg.clone(),
-            stack: vec![],
+        let mut html = HtmlProcessor {
+            cfg: new_cfg,
+            stack: self.stack.clone(),
             content: vec![],
+            parent: None,
+            read: vec![],
+        };
+        let handle = html.markdown(path, handle);
+        self.read.append(&mut html.read);
+        handle
+    }
+}
+
+fn eval(expr: &str, vars: &VarStack, path: &str) -> Value {
+    match crate::expr::eval(expr, vars) {
+        Ok(value) => value,
+        Err(err) => fatal!(
+            "Invalid expression; expression={}; error={}; path={}",
+            expr,
+            err,
+            path
+        ),
+    }
+}
+
+/// Like `eval`, but the result has to be set
+fn eval_value(expr: &str, vars: &VarStack, path: &str) -> Value {
+    match eval(expr, vars, path) {
+        Value::Null => fatal!("Variable not found; expression={}; path={}", expr, path),
+        value => value,
+    }
+}
+
+/// Replaces `{{EXPR}}` placeholders, `\{{` is a literal `{{`. Placeholders that can't be
+/// evaluated, like the ones of client side templates, are left as they are.
+pub fn interpolate(value: &str, vars: &VarStack, path: &str) -> String {
+    let mut interpolated = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("{{") {
+        if let Some(before) = rest[..start].strip_suffix('\\') {
+            interpolated.push_str(before);
+            interpolated.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
         }
-        .markdown(path, handle)
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find("}}") {
+            Some(end) => end + 2,
+            None => break,
+        };
+        let (placeholder, expr) = (&rest[..end], &rest[2..end - 2]);
+        match crate::expr::eval(expr, vars) {
+            Ok(Value::Null) => {
+                tracing::warn!(
+                    "Variable not found, leaving placeholder as is; expression={}; path={}",
+                    expr.trim(),
+                    path
+                );
+                interpolated.push_str(placeholder);
+            }
+            Ok(value) => interpolated.push_str(&value.to_string()),
+            Err(err) => {
+                tracing::warn!(
+                    "Invalid expression, leaving placeholder as is; expression={}; error={}; path={}",
+                    expr.trim(),
+                    err,
+                    path
+                );
+                interpolated.push_str(placeholder);
+            }
+        }
+        rest = &rest[end..];
+    }
+    interpolated.push_str(rest);
+    interpolated
+}
+
+/// Appends a processed include to `handle`
+fn insert(handle: &Handle, include: Handle) {
+    if let NodeData::Document = &include.data {
+        handle
+            .children
+            .borrow_mut()
+            .append(&mut include.children.take());
+    } else {
+        handle.children.borrow_mut().push(include);
+    }
+}
+
+/// Adds every html file in `dir` to the registered components, registered ones take precedence.
+/// Names are lowercased, html tag names are case insensitive.
+pub fn components(registered: HashMap<String, PathBuf>, dir: &Path) -> HashMap<String, PathBuf> {
+    let mut components = HashMap::with_capacity(registered.len());
+    for (name, path) in registered {
+        let lower = name.to_lowercase();
+        if components.insert(lower.clone(), path).is_some() {
+            fatal!(
+                "Component registered twice, names are case insensitive; name={}",
+                lower
+            );
+        }
+    }
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return components,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("html") {
+            continue;
+        }
+        if let Some(name) = path.file_stem() {
+            let name = name.to_string_lossy().to_lowercase();
+            tracing::trace!(
+                "Found component; name={}; path={}",
+                name,
+                path.to_string_lossy()
+            );
+            components.entry(name).or_insert(path);
+        }
+    }
+    components
+}
+
+/// Lines `first` to `last` of `src`, both included
+fn slice_lines(src: &str, (first, last): (usize, usize), path: &Path) -> String {
+    let count = src.lines().count();
+    if first > count {
+        tracing::warn!(
+            "Line range starts after the end of the file; first={}; lines={}; path={}",
+            first,
+            count,
+            path.to_string_lossy()
+        );
     }
+    src.lines()
+        .skip(first - 1)
+        .take(last - first + 1)
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn dependencies(mut read: Vec<PathBuf>, path: &Path) -> Vec<PathBuf> {
+    read.retain(|dep| dep != path);
+    read.sort();
+    read.dedup();
+    read
+}
+
+pub fn element(name: &str, attrs: Vec<Attribute>, children: Vec<Handle>) -> Handle {
+    Handle::new(Node {
+        parent: Cell::new(None),
+        children: RefCell::new(children),
+        data: NodeData::Element {
+            name: QualName::new(None, ns!(html), string_cache::Atom::from(name)),
+            attrs: RefCell::new(attrs),
+            template_contents: None,
+            mathml_annotation_xml_integration_point: false,
+        },
+    })
+}
+
+pub fn attribute(name: &str, value: &str) -> Attribute {
+    Attribute {
+        name: QualName::new(None, ns!(), string_cache::Atom::from(name)),
+        value: StrTendril::from(value),
+    }
+}
+
+pub fn text(text: &str) -> Handle {
+    Handle::new(Node {
+        parent: Cell::new(None),
+        children: RefCell::new(vec![]),
+        data: NodeData::Text {
+            contents: RefCell::new(StrTendril::from(text)),
+        },
+    })
+}
+
+/// Local name of html elements, empty for everything else
+pub fn element_name(handle: &Handle) -> &str {
+    match &handle.data {
+        NodeData::Element { name, .. } if name.ns == ns!(html) => &name.local,
+        _ => "",
+    }
+}
+
+fn is_whitespace(handle: &Handle) -> bool {
+    match &handle.data {
+        NodeData::Text { contents } => contents.borrow().trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// First element with the given name, in document order
+pub fn find_element(handle: &Handle, name: &str) -> Option<Handle> {
+    if element_name(handle) == name {
+        return Some(handle.clone());
+    }
+    handle
+        .children
+        .borrow()
+        .iter()
+        .find_map(|child| find_element(child, name))
+}
+
+fn take_attr(attrs: &mut Vec<Attribute>, name: &str) -> Option<String> {
+    let i = attrs.iter().position(|attr| attr.name.local == *name)?;
+    Some(attrs.remove(i).value.to_string())
 }
 
 fn read_file(path: &Path) -> String {
@@ -0,0 +1,78 @@
+use crate::config::RedirectMap;
+use crate::writer::Enqueuer;
+use std::path::PathBuf;
+
+pub struct Redirects {
+    writer: Enqueuer,
+    entries: Vec<(String, String)>,
+}
+
+impl Redirects {
+    pub fn new(writer: Enqueuer) -> Self {
+        Self {
+            writer,
+            entries: vec![],
+        }
+    }
+
+    /// Writes a redirect page at `from` pointing to `to`
+    pub fn add(&mut self, from: &str, to: &str, src: PathBuf) {
+        let from = from.trim().trim_start_matches('/');
+        if from.is_empty() {
+            tracing::warn!("Empty redirect path, skipping; to={}", to);
+            return;
+        }
+        let file = if from.ends_with('/') || !from.rsplit('/').next().unwrap().contains('.') {
+            format!("{}/index.html", from.trim_end_matches('/'))
+        } else {
+            from.to_string()
+        };
+        tracing::trace!("Adding redirect; from={}; to={}", from, to);
+        self.writer.file(PathBuf::from(file), page(to), src);
+        self.entries.push((format!("/{}", from), to.to_string()));
+    }
+
+    /// Writes a map of all redirects for servers which can redirect on their own
+    pub fn write_map(&self, map: &RedirectMap) {
+        let (path, line): (_, fn(&str, &str) -> String) = match map {
+            RedirectMap::Netlify => (PathBuf::from("_redirects"), |from, to| {
+                format!("{} {} 301\n", from, to)
+            }),
+            RedirectMap::Nginx(path) => (PathBuf::from(path), |from, to| {
+                format!("{} {};\n", from, to)
+            }),
+        };
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort();
+        let map = entries
+            .into_iter()
+            .map(|(from, to)| line(from, to))
+            .collect::<String>();
+        self.writer
+            .file(path, map, PathBuf::from(crate::CONFIG_FILE));
+    }
+}
+
+fn page(to: &str) -> String {
+    let to = escape(to);
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Redirecting...</title>\
+         <link rel=\"canonical\" href=\"{0}\"><meta http-equiv=\"refresh\" content=\"0; url={0}\">\
+         </head><body><a href=\"{0}\">{0}</a></body></html>\n",
+        to
+    )
+}
+
+fn escape(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    for ch in str.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
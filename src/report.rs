@@ -0,0 +1,64 @@
+use crate::fatal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Machine readable summary of a build
+#[derive(Serialize, Default)]
+pub struct Report {
+    /// Nothing was actually written, `files` and `removed` describe what would have happened
+    pub dry_run: bool,
+    pub files: Vec<FileReport>,
+    /// Stale files of the previous build
+    pub removed: Vec<PathBuf>,
+}
+
+#[derive(Serialize)]
+pub struct FileReport {
+    /// Relative to dist
+    pub path: PathBuf,
+    pub source: PathBuf,
+    pub size: usize,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<PathBuf>,
+    /// Only known for pages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub render_ms: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Written,
+    Unchanged,
+}
+
+/// What is known about a page before it is handed to the writer
+pub struct PageStats {
+    pub dependencies: Vec<PathBuf>,
+    pub render_time: Duration,
+}
+
+impl Report {
+    pub fn add_pages(&mut self, mut pages: HashMap<PathBuf, PageStats>) {
+        for file in &mut self.files {
+            if let Some(stats) = pages.remove(&file.path) {
+                file.dependencies = stats.dependencies;
+                file.render_ms = Some(stats.render_time.as_secs_f64() * 1000.0);
+            }
+        }
+    }
+
+    pub fn write(&self, path: &Path) {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        if let Err(err) = std::fs::write(path, json) {
+            fatal!(
+                "Unable to write build report; path={}; error={}",
+                path.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::fatal;
+use std::path::Path;
+use tracing::{info, trace};
+
+/// Built in project templates for `supersolid new`
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Starter {
+    /// Posts with a date, listed on the index page
+    Blog,
+    /// Recipes with servings and time, like based.cooking
+    Recipes,
+}
+
+macro_rules! starter {
+    ($name:literal, [$($file:literal),* $(,)?]) => {
+        &[$(($file, include_str!(concat!("../starters/", $name, "/", $file)))),*]
+    };
+}
+
+impl Starter {
+    /// Relative path and content of every file
+    fn files(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Starter::Blog => starter!(
+                "blog",
+                [
+                    ".gitignore",
+                    "config.ron",
+                    "src/base.html",
+                    "src/post.html",
+                    "src/index.html",
+                    "src/posts/hello-world.md",
+                    "src/posts/writing-posts.md",
+                    "static/style.css",
+                ]
+            ),
+            Starter::Recipes => starter!(
+                "recipes",
+                [
+                    ".gitignore",
+                    "config.ron",
+                    "src/base.html",
+                    "src/recipe.html",
+                    "src/index.html",
+                    "src/recipes/pancakes.md",
+                    "src/recipes/tomato-soup.md",
+                    "static/style.css",
+                ]
+            ),
+        }
+    }
+}
+
+/// Creates a new project at `path`, which must not exist or be empty
+pub fn create(path: &Path, starter: Starter) {
+    let occupied = match path.read_dir() {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => path.exists(),
+    };
+    if occupied {
+        fatal!(
+            "Path already exists and is not empty; path={}",
+            path.to_string_lossy()
+        );
+    }
+
+    for (file, content) in starter.files() {
+        let file = path.join(file);
+        trace!("Creating file; path={}", file.to_string_lossy());
+        if let Err(err) = std::fs::create_dir_all(file.parent().unwrap())
+            .and_then(|_| std::fs::write(&file, content))
+        {
+            fatal!(
+                "Unable to create file; path={}; error={}",
+                file.to_string_lossy(),
+                err
+            );
+        }
+    }
+    info!(
+        "Created new project, build it with `supersolid build {}`",
+        path.to_string_lossy()
+    );
+}
@@ -0,0 +1,49 @@
+use crate::fatal;
+use crate::writer::Enqueuer;
+use grass_compiler::codemap::SpanLoc;
+use std::path::{Path, PathBuf};
+
+/// Compiles `src` into `output`.
+/// Returns the path the stylesheet was written to, which differs from `output` when fingerprinted.
+pub fn compile(
+    src: &str,
+    output: &str,
+    minify: bool,
+    fingerprint: bool,
+    load_paths: &[PathBuf],
+    writer: &Enqueuer,
+) -> PathBuf {
+    let options = grass_compiler::Options::default()
+        .logger(&TracingLogger)
+        .load_paths(load_paths)
+        .style(if minify {
+            grass_compiler::OutputStyle::Compressed
+        } else {
+            grass_compiler::OutputStyle::Expanded
+        });
+    let css = match grass_compiler::from_path(src, &options) {
+        Ok(css) => css,
+        Err(err) => fatal!("Unable to compile scss; path={}; error={}", src, err),
+    };
+
+    let output = if fingerprint {
+        crate::asset::fingerprinted(Path::new(output), css.as_bytes())
+    } else {
+        PathBuf::from(output)
+    };
+    writer.file(output.clone(), css, src.into());
+    output
+}
+
+#[derive(Debug)]
+struct TracingLogger;
+
+impl grass_compiler::Logger for TracingLogger {
+    fn debug(&self, location: SpanLoc, message: &str) {
+        tracing::debug!("Scss debug; location={}; message={}", location, message);
+    }
+
+    fn warn(&self, location: SpanLoc, message: &str) {
+        tracing::warn!("Scss warning; location={}; message={}", location, message);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::fatal;
+use std::path::{Component, Path, PathBuf};
+use tiny_http::{Header, Response, Server};
+use tracing::{debug, info, warn};
+
+/// Serves the files in `dist` until the process is killed
+pub fn serve(dist: &Path, host: &str, port: u16) {
+    let server = match Server::http((host, port)) {
+        Ok(server) => server,
+        Err(err) => fatal!(
+            "Unable to start server; host={}; port={}; error={}",
+            host,
+            port,
+            err
+        ),
+    };
+    info!(
+        "Serving {} on http://{}:{}/",
+        dist.to_string_lossy(),
+        host,
+        port
+    );
+
+    for request in server.incoming_requests() {
+        let path = request.url().split(['?', '#']).next().unwrap().to_string();
+        let response = match resolve(dist, &path).map(|file| (std::fs::read(&file), file)) {
+            Some((Ok(data), file)) => {
+                debug!(
+                    "Serving file; url={}; path={}",
+                    path,
+                    file.to_string_lossy()
+                );
+                let mime = Header::from_bytes("Content-Type", mime(&file)).unwrap();
+                Response::from_data(data).with_header(mime)
+            }
+            _ => {
+                debug!("File not found; url={}", path);
+                let page = dist.join("404.html");
+                Response::from_data(std::fs::read(page).unwrap_or_else(|_| b"Not found".to_vec()))
+                    .with_header(
+                        Header::from_bytes("Content-Type", mime(Path::new("404.html"))).unwrap(),
+                    )
+                    .with_status_code(404)
+            }
+        };
+        if let Err(err) = request.respond(response) {
+            warn!("Unable to send response; url={}; error={}", path, err);
+        }
+    }
+}
+
+/// File for an url path, directories are served by their `index.html`
+fn resolve(dist: &Path, url: &str) -> Option<PathBuf> {
+    let url = decode(url)?;
+    let relative = Path::new(url.trim_start_matches('/'));
+    // Never serve anything outside of dist, checked after decoding so `%2e%2e` is caught too
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let path = dist.join(relative);
+    if path.is_dir() {
+        Some(path.join("index.html")).filter(|path| path.is_file())
+    } else if path.is_file() {
+        Some(path)
+    } else {
+        Some(path.with_extension("html")).filter(|path| path.is_file())
+    }
+}
+
+/// Decodes `%XX` escapes, `None` for malformed escapes or invalid utf-8
+fn decode(url: &str) -> Option<String> {
+    let bytes = url.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            out.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn mime(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") | Some("map") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,379 @@
+use crate::fatal;
+use flate2::write::GzEncoder;
+use flate2::GzBuilder;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Where the writer puts generated files
+pub trait Sink: Send {
+    fn write(&mut self, path: &Path, data: &[u8]);
+
+    /// Copies a file that exists on disk, `data` is its current content
+    fn copy(&mut self, _from: &Path, to: &Path, data: &[u8]) {
+        self.write(to, data)
+    }
+
+    /// Content of a file left behind by a previous build, unchanged files aren't written again
+    fn existing(&self, _path: &Path) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Files produced by the previous build
+    fn previous(&self) -> Vec<PathBuf> {
+        vec![]
+    }
+
+    /// Removes a stale file of the previous build
+    fn remove(&mut self, _path: &Path) {}
+
+    /// Called once everything is written, with every file produced by this build
+    fn finish(&mut self, _produced: &[&Path]) {}
+}
+
+/// Lists every file produced by the last build, so stale ones can be removed by the next
+const RECORD_FILE: &str = ".supersolid";
+
+/// Writes into a directory
+pub struct FsSink {
+    dir: PathBuf,
+}
+
+impl FsSink {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl Sink for FsSink {
+    fn write(&mut self, path: &Path, data: &[u8]) {
+        let file = self.dir.join(path);
+        let parent = file.parent().unwrap();
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            fatal!(
+                "Unable to create dir; path={}; error={}",
+                parent.to_string_lossy(),
+                err
+            );
+        }
+        // The dir may be a staging dir, containing hard links to the live dist
+        let _ = std::fs::remove_file(&file);
+        if let Err(err) = std::fs::write(&file, data) {
+            fatal!(
+                "Unable to write file; path={}; error={}",
+                file.to_string_lossy(),
+                err
+            );
+        };
+    }
+
+    fn existing(&self, path: &Path) -> Option<Vec<u8>> {
+        std::fs::read(self.dir.join(path)).ok()
+    }
+
+    fn previous(&self) -> Vec<PathBuf> {
+        match std::fs::read_to_string(self.dir.join(RECORD_FILE)) {
+            Ok(record) => record.lines().map(PathBuf::from).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        let file = self.dir.join(path);
+        match std::fs::remove_file(&file) {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(err) => fatal!(
+                "Unable to remove stale file; path={}; error={}",
+                file.to_string_lossy(),
+                err
+            ),
+        }
+        // Clean up directories that only contained stale files
+        for dir in file.ancestors().skip(1).take_while(|dir| *dir != self.dir) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn finish(&mut self, produced: &[&Path]) {
+        let mut record = produced
+            .iter()
+            .map(|path| path.to_string_lossy())
+            .collect::<Vec<_>>();
+        record.sort();
+        let record = record
+            .iter()
+            .map(|path| format!("{}\n", path))
+            .collect::<String>();
+        let path = self.dir.join(RECORD_FILE);
+        if let Err(err) = std::fs::write(&path, record) {
+            fatal!(
+                "Unable to write file; path={}; error={}",
+                path.to_string_lossy(),
+                err
+            );
+        }
+    }
+}
+
+/// Keeps everything in memory, clones share the same files
+#[derive(Clone, Default)]
+pub struct MemorySink(Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>);
+
+impl MemorySink {
+    pub fn files(&self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Sink for MemorySink {
+    fn write(&mut self, path: &Path, data: &[u8]) {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), data.to_vec());
+    }
+
+    fn existing(&self, path: &Path) -> Option<Vec<u8>> {
+        self.0.lock().unwrap().get(path).cloned()
+    }
+}
+
+/// Collects everything into a single archive file.
+/// Files are sorted and get fixed timestamps and permissions, so the same site always results
+/// in the same archive. Sorting means nothing can be written before the build is done, so
+/// generated files are held in memory until then, copied files are read from their source when
+/// the archive is written. The archive is written next to its final path and
+/// only renamed once complete, a failed build leaves the previous archive untouched.
+pub struct ArchiveSink {
+    path: PathBuf,
+    format: Format,
+    /// By name inside the archive, written out in order once the build is done
+    files: BTreeMap<String, Entry>,
+    /// Seconds since the unix epoch, taken from `SOURCE_DATE_EPOCH` like other reproducible builds
+    mtime: u64,
+}
+
+enum Entry {
+    Data(Vec<u8>),
+    /// Copied file, only read when it is added to the archive
+    Copy(PathBuf),
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    TarGz,
+    Zip,
+}
+
+enum Archive {
+    TarGz(tar::Builder<GzEncoder<File>>),
+    Zip(zip::ZipWriter<File>),
+}
+
+impl ArchiveSink {
+    /// The format is decided by the extension, `.tar.gz`, `.tgz` or `.zip`
+    pub fn new(path: PathBuf) -> Self {
+        let name = path.to_string_lossy().to_lowercase();
+        let format = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Format::TarGz
+        } else if name.ends_with(".zip") {
+            Format::Zip
+        } else {
+            fatal!(
+                "Unknown archive format, expected .tar.gz, .tgz or .zip; path={}",
+                path.to_string_lossy()
+            );
+        };
+        let mtime = match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(epoch) => match epoch.trim().parse::<u64>() {
+                Ok(epoch) => epoch,
+                Err(err) => fatal!("Invalid SOURCE_DATE_EPOCH; value={}; error={}", epoch, err),
+            },
+            Err(_) => 0,
+        };
+        // Gzip only has room for 32 bit timestamps, zip dates end in 2107 anyway
+        if u32::try_from(mtime).is_err() {
+            fatal!("SOURCE_DATE_EPOCH is out of range; value={}", mtime);
+        }
+        Self {
+            path,
+            format,
+            files: BTreeMap::new(),
+            mtime,
+        }
+    }
+
+    /// Hidden file next to the archive, `site.zip` is written as `.site.zip.partial`
+    fn partial_path(&self) -> PathBuf {
+        let name = self.path.file_name().unwrap().to_string_lossy();
+        self.path.with_file_name(format!(".{}.partial", name))
+    }
+
+    fn create(&self, file: File) -> Archive {
+        match self.format {
+            Format::TarGz => {
+                // The gzip header would otherwise contain the current time
+                let gz = GzBuilder::new()
+                    .mtime(self.mtime as u32)
+                    .write(file, flate2::Compression::default());
+                let mut tar = tar::Builder::new(gz);
+                tar.mode(tar::HeaderMode::Deterministic);
+                Archive::TarGz(tar)
+            }
+            Format::Zip => Archive::Zip(zip::ZipWriter::new(file)),
+        }
+    }
+}
+
+impl Archive {
+    fn append(&mut self, name: &str, entry: Entry, mtime: u64) -> std::io::Result<()> {
+        let (size, mut data): (u64, Box<dyn Read>) = match entry {
+            Entry::Data(data) => (data.len() as u64, Box::new(Cursor::new(data))),
+            Entry::Copy(path) => {
+                let file = File::open(path)?;
+                (file.metadata()?.len(), Box::new(file))
+            }
+        };
+        match self {
+            Archive::TarGz(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(size);
+                header.set_mode(0o644);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                tar.append_data(&mut header, name, data)
+            }
+            Archive::Zip(zip) => {
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .unix_permissions(0o644)
+                    .last_modified_time(zip_time(mtime));
+                zip.start_file(name, options)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| std::io::copy(&mut data, zip))
+                    .map(|_| ())
+            }
+        }
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Archive::TarGz(tar) => tar.into_inner().and_then(|gz| gz.finish()).map(|_| ()),
+            Archive::Zip(zip) => zip.finish().map(|_| ()).map_err(std::io::Error::from),
+        }
+    }
+}
+
+impl Sink for ArchiveSink {
+    fn write(&mut self, path: &Path, data: &[u8]) {
+        self.files
+            .insert(entry_name(path), Entry::Data(data.to_vec()));
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path, _data: &[u8]) {
+        self.files
+            .insert(entry_name(to), Entry::Copy(from.to_path_buf()));
+    }
+
+    fn finish(&mut self, _produced: &[&Path]) {
+        let partial = self.partial_path();
+        let mut archive = match File::create(&partial) {
+            Ok(file) => self.create(file),
+            Err(err) => fatal!(
+                "Unable to create archive; path={}; error={}",
+                partial.to_string_lossy(),
+                err
+            ),
+        };
+        for (name, entry) in std::mem::take(&mut self.files) {
+            if let Err(err) = archive.append(&name, entry, self.mtime) {
+                let _ = std::fs::remove_file(&partial);
+                fatal!(
+                    "Unable to add file to archive; path={}; archive={}; error={}",
+                    name,
+                    self.path.to_string_lossy(),
+                    err
+                );
+            }
+        }
+        if let Err(err) = archive
+            .finish()
+            .and_then(|_| std::fs::rename(&partial, &self.path))
+        {
+            let _ = std::fs::remove_file(&partial);
+            fatal!(
+                "Unable to write archive; path={}; error={}",
+                self.path.to_string_lossy(),
+                err
+            );
+        }
+        tracing::info!("Wrote archive {}", self.path.to_string_lossy());
+    }
+}
+
+/// Archives always use forward slashes
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Zip timestamps are calendar dates, starting in 1980
+fn zip_time(epoch: u64) -> zip::DateTime {
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (epoch / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as u16;
+
+    let secs = epoch % 86400;
+    zip::DateTime::from_date_and_time(
+        year,
+        month,
+        day,
+        (secs / 3600) as u8,
+        (secs / 60 % 60) as u8,
+        (secs % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zip_time_is_a_calendar_date() {
+        let time = zip_time(1_614_862_800);
+        assert_eq!(
+            (
+                time.year(),
+                time.month(),
+                time.day(),
+                time.hour(),
+                time.minute(),
+                time.second()
+            ),
+            (2021, 3, 4, 13, 0, 0)
+        );
+        let time = zip_time(951_782_400);
+        assert_eq!((time.year(), time.month(), time.day()), (2000, 2, 29));
+    }
+
+    #[test]
+    fn zip_time_starts_in_1980() {
+        assert_eq!(zip_time(0), zip::DateTime::default());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::fatal;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory a build is written to before it replaces dist
+pub fn staging_path(dist: &Path) -> PathBuf {
+    sibling(dist, "staging")
+}
+
+/// Creates the staging directory for `dist`, filled with hard links to the current files so
+/// unchanged and foreign files carry over without copying them
+pub fn prepare(dist: &Path) -> PathBuf {
+    let staging = staging_path(dist);
+    if staging.exists() {
+        tracing::warn!(
+            "Removing staging dir left behind by a failed build; path={}",
+            staging.to_string_lossy()
+        );
+        remove_dir(&staging);
+    }
+    let res = if dist.exists() {
+        link_tree(dist, &staging)
+    } else {
+        std::fs::create_dir_all(&staging)
+    };
+    if let Err(err) = res {
+        fatal!(
+            "Unable to create staging dir; path={}; error={}",
+            staging.to_string_lossy(),
+            err
+        );
+    }
+    staging
+}
+
+/// Puts a finished build in place of dist.
+/// A symlinked dist is replaced atomically by pointing it to the new build. A plain directory is
+/// exchanged with the build in one step on Linux, elsewhere it is moved aside first and put back if
+/// the build can't take its place.
+pub fn swap(staging: &Path, dist: &Path) {
+    tracing::debug!(
+        "Swapping build into place; staging={}; dist={}",
+        staging.to_string_lossy(),
+        dist.to_string_lossy()
+    );
+    match std::fs::symlink_metadata(dist) {
+        Ok(meta) if meta.file_type().is_symlink() => swap_symlink(staging, dist),
+        Ok(_) => {
+            #[cfg(target_os = "linux")]
+            {
+                match exchange(staging, dist) {
+                    // Staging now holds the previous build
+                    Ok(()) => return cleanup(staging),
+                    Err(err) => tracing::debug!(
+                        "Unable to exchange dirs, moving dist aside instead; error={}",
+                        err
+                    ),
+                }
+            }
+            let old = sibling(dist, "old");
+            if old.exists() {
+                remove_dir(&old);
+            }
+            rename(dist, &old);
+            if let Err(err) = std::fs::rename(staging, dist) {
+                if let Err(err) = std::fs::rename(&old, dist) {
+                    tracing::error!(
+                        "Unable to restore previous build; path={}; error={}",
+                        old.to_string_lossy(),
+                        err
+                    );
+                }
+                fatal!(
+                    "Unable to move dir; from={}; to={}; error={}",
+                    staging.to_string_lossy(),
+                    dist.to_string_lossy(),
+                    err
+                );
+            }
+            cleanup(&old);
+        }
+        Err(_) => rename(staging, dist),
+    }
+}
+
+/// Atomically swaps two directories
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    let res = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+fn swap_symlink(staging: &Path, dist: &Path) {
+    let previous = match std::fs::read_link(dist) {
+        Ok(target) => dist.parent().unwrap().join(target),
+        Err(err) => fatal!(
+            "Unable to read dist symlink; path={}; error={}",
+            dist.to_string_lossy(),
+            err
+        ),
+    };
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let release = sibling(dist, &nanos.to_string());
+    rename(staging, &release);
+
+    let link = sibling(dist, "link");
+    let _ = std::fs::remove_file(&link);
+    if let Err(err) = symlink(Path::new(release.file_name().unwrap()), &link) {
+        fatal!(
+            "Unable to create symlink; path={}; error={}",
+            link.to_string_lossy(),
+            err
+        );
+    }
+    rename(&link, dist);
+
+    // Only builds created by us are cleaned up, never a directory the symlink was pointed to by hand
+    let prefix = sibling(dist, "").to_string_lossy().to_string();
+    if previous.to_string_lossy().starts_with(&prefix) {
+        cleanup(&previous);
+    } else {
+        tracing::info!(
+            "Dist now points to a new build, leaving the previous target alone; path={}",
+            previous.to_string_lossy()
+        );
+    }
+}
+
+/// Hidden path next to dist, `dist` becomes `.dist.<suffix>`
+fn sibling(dist: &Path, suffix: &str) -> PathBuf {
+    let name = dist.file_name().unwrap().to_string_lossy();
+    dist.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+fn link_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_tree(&entry.path(), &target)?;
+        } else if std::fs::hard_link(entry.path(), &target).is_err() {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+fn rename(from: &Path, to: &Path) {
+    if let Err(err) = std::fs::rename(from, to) {
+        fatal!(
+            "Unable to move dir; from={}; to={}; error={}",
+            from.to_string_lossy(),
+            to.to_string_lossy(),
+            err
+        );
+    }
+}
+
+fn remove_dir(path: &Path) {
+    if let Err(err) = std::fs::remove_dir_all(path) {
+        fatal!(
+            "Unable to remove dir; path={}; error={}",
+            path.to_string_lossy(),
+            err
+        );
+    }
+}
+
+/// Removes a replaced build, the new one is already in place so failing isn't fatal
+fn cleanup(path: &Path) {
+    if let Err(err) = std::fs::remove_dir_all(path) {
+        tracing::warn!(
+            "Unable to remove previous build; path={}; error={}",
+            path.to_string_lossy(),
+            err
+        );
+    }
+}
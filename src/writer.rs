@@ -1,137 +1,269 @@
+use crate::config::CollisionPolicy;
+use crate::report::{FileReport, Report, Status};
+use crate::sink::{ArchiveSink, FsSink, MemorySink, Sink};
+use glob::Pattern;
+use path_clean::PathClean;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread::JoinHandle;
 
 pub struct Writer {
-    th: Option<JoinHandle<()>>,
+    th: Option<JoinHandle<Report>>,
+    /// Staging dir and the dist it replaces once the build is done
+    swap: Option<(PathBuf, PathBuf)>,
+}
+
+/// Where a build goes
+pub enum Target {
+    /// Synced into dist through a staging dir
+    Dist(PathBuf),
+    /// Compared against dist, without writing anything
+    DryRun(PathBuf),
+    Memory(MemorySink),
+    /// A `.tar.gz` or `.zip` file
+    Archive(PathBuf),
 }
 
 struct InnerWriter {
-    out_dir: PathBuf,
+    sink: Box<dyn Sink>,
+    /// Only record what would be written, without touching the sink
+    dry_run: bool,
     recv: Receiver<Command>,
+    collisions: CollisionPolicy,
+    /// Every output path written so far, mapped to the source that produced it
+    produced: HashMap<PathBuf, PathBuf>,
+    /// Files produced by the previous build
+    previous: HashSet<PathBuf>,
+    /// Files in dist that are never removed
+    keep: Vec<Pattern>,
+    report: Report,
 }
 
 #[derive(Clone)]
 pub struct Enqueuer(Sender<Command>);
 
 enum Command {
-    Write(PathBuf, String),
+    /// Output path, content, source
+    Write(PathBuf, Vec<u8>, PathBuf),
     Copy(PathBuf, PathBuf),
 }
 
 impl Writer {
-    pub fn new(path: PathBuf) -> (Writer, Enqueuer) {
+    pub fn new(
+        target: Target,
+        collisions: CollisionPolicy,
+        keep: Vec<Pattern>,
+    ) -> (Writer, Enqueuer) {
+        let dry_run = matches!(target, Target::DryRun(_));
+        let (sink, swap): (Box<dyn Sink>, _) = match target {
+            Target::Dist(dist) => {
+                tracing::info!("Outputting into {}", dist.to_string_lossy());
+                let staging = crate::staging::prepare(&dist);
+                (
+                    Box::new(FsSink::new(staging.clone())),
+                    Some((staging, dist)),
+                )
+            }
+            Target::DryRun(dist) => {
+                tracing::info!("Dry run, comparing against {}", dist.to_string_lossy());
+                (Box::new(FsSink::new(dist)), None)
+            }
+            Target::Memory(sink) => (Box::new(sink), None),
+            Target::Archive(path) => (Box::new(ArchiveSink::new(path)), None),
+        };
         let (tx, rx) = std::sync::mpsc::channel();
-        let writer = InnerWriter {
-            out_dir: path,
+        let mut writer = InnerWriter {
+            previous: sink.previous().into_iter().collect(),
+            sink,
+            dry_run,
             recv: rx,
+            collisions,
+            produced: HashMap::new(),
+            keep,
+            report: Report {
+                dry_run,
+                ..Default::default()
+            },
         };
         (
             Writer {
                 th: Some(std::thread::spawn(move || {
-                    writer.init();
-                    for cmd in writer.recv.iter() {
+                    while let Ok(cmd) = writer.recv.recv() {
                         match cmd {
-                            Command::Write(path, data) => writer.write(&path, &data),
-                            Command::Copy(from, to) => writer.copy(&from, &to),
+                            Command::Write(path, data, src) => {
+                                writer.track(&path, &src);
+                                writer.write(&path, &data, &src)
+                            }
+                            Command::Copy(from, to) => {
+                                writer.track(&to, &from);
+                                writer.copy(&from, &to)
+                            }
                         }
                     }
+                    writer.finish();
+                    writer.report
                 })),
+                swap,
             },
             Enqueuer(tx),
         )
     }
 
-    pub fn join(self) {
-        drop(self);
+    /// Waits for everything to be written and puts the build in place of dist
+    pub fn join(mut self) -> Report {
+        let report = match self.th.take().map(JoinHandle::join) {
+            Some(Ok(report)) => report,
+            _ => crate::fatal!("Writer thread panicked, keeping the previous dist"),
+        };
+        if let Some((staging, dist)) = self.swap.take() {
+            crate::staging::swap(&staging, &dist);
+        }
+        report
     }
 }
 
 impl InnerWriter {
-    fn init(&self) {
-        tracing::info!("Outputting into {}", self.out_dir.to_string_lossy());
-        if self.out_dir.exists() {
-            tracing::warn!("Deleting old dist");
-            if let Err(err) = std::fs::remove_dir_all(&self.out_dir) {
-                crate::fatal!(
-                    "Failed to delete old dist dir; path={}; error={}",
-                    &self.out_dir.to_string_lossy(),
-                    err
-                );
+    /// Removes files of the previous build that weren't produced again and finishes the sink
+    fn finish(&mut self) {
+        let mut stale = self
+            .previous
+            .iter()
+            .filter(|path| !self.produced.contains_key(*path))
+            .filter(|path| !self.keep.iter().any(|keep| keep.matches_path(path)))
+            .filter(|path| self.sink.existing(path).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        stale.sort();
+        for path in stale {
+            if self.dry_run {
+                tracing::info!("Would remove stale file; path={}", path.to_string_lossy());
+            } else {
+                tracing::debug!("Removing stale file; path={}", path.to_string_lossy());
+                self.sink.remove(&path);
             }
+            self.report.removed.push(path);
         }
-        tracing::trace!(
-            "Creating new dist dir; path={}",
-            self.out_dir.to_string_lossy()
-        );
-        if let Err(err) = std::fs::create_dir_all(&self.out_dir) {
-            crate::fatal!(
-                "Unable to create output dir; path={}; error={}",
-                &self.out_dir.to_string_lossy(),
-                err
+
+        let written = self
+            .report
+            .files
+            .iter()
+            .filter(|file| file.status == Status::Written)
+            .count();
+        let unchanged = self.report.files.len() - written;
+        if self.dry_run {
+            tracing::info!(
+                "Dry run, nothing written; written={}; unchanged={}; removed={}",
+                written,
+                unchanged,
+                self.report.removed.len()
             );
+            return;
         }
-    }
 
-    fn write(&self, path: &Path, data: &str) {
-        let path = self.path(path);
-        tracing::trace!(
-            "Writing file; path={}; len={}",
-            path.to_string_lossy(),
-            data.len()
+        let produced = self
+            .produced
+            .keys()
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>();
+        self.sink.finish(&produced);
+        tracing::info!(
+            "Synced output; written={}; unchanged={}; removed={}",
+            written,
+            unchanged,
+            self.report.removed.len()
         );
-        if let Err(err) = std::fs::write(&path, data) {
-            crate::fatal!(
-                "Unable to write file; path={}; error={}",
-                path.to_string_lossy(),
-                err
-            );
+    }
+
+    fn track(&mut self, path: &Path, src: &Path) {
+        let prev = match self
+            .produced
+            .insert(path.to_path_buf().clean(), src.to_path_buf())
+        {
+            Some(prev) => prev,
+            None => return,
         };
+        match self.collisions {
+            CollisionPolicy::Error => crate::fatal!(
+                "Output path collision; path={}; first={}; second={}",
+                path.to_string_lossy(),
+                prev.to_string_lossy(),
+                src.to_string_lossy()
+            ),
+            CollisionPolicy::Warn => tracing::warn!(
+                "Output path collision, overwriting; path={}; first={}; second={}",
+                path.to_string_lossy(),
+                prev.to_string_lossy(),
+                src.to_string_lossy()
+            ),
+        }
     }
 
-    fn copy(&self, from: &Path, to: &Path) {
-        let to = self.path(to);
-        if let Err(err) = std::fs::copy(from, &to) {
-            crate::fatal!(
-                "Unable to copy file into; from={}; to={}; error={}",
+    fn write(&mut self, path: &Path, data: &[u8], src: &Path) {
+        if self.record(path, data, src) == Status::Written && !self.dry_run {
+            self.sink.write(path, data);
+        }
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path) {
+        let data = match std::fs::read(from) {
+            Ok(data) => data,
+            Err(err) => crate::fatal!(
+                "Unable to copy file; from={}; to={}; error={}",
                 from.to_string_lossy(),
                 to.to_string_lossy(),
                 err
-            );
+            ),
+        };
+        if self.record(to, &data, from) == Status::Written && !self.dry_run {
+            self.sink.copy(from, to, &data);
         }
     }
 
-    fn path(&self, path: &Path) -> PathBuf {
-        let path = self.out_dir.join(path);
-        let parent = path.parent().unwrap();
-        if !parent.exists() {
-            if let Err(err) = std::fs::create_dir_all(parent) {
-                crate::fatal!(
-                    "Unable to create dir; path={}; error={}",
-                    parent.to_string_lossy(),
-                    err
-                );
+    /// Adds a file to the report, files that already have this content don't need to be written
+    fn record(&mut self, path: &Path, data: &[u8], src: &Path) -> Status {
+        let status = match self.sink.existing(path) {
+            Some(old) if crate::asset::hash(&old) == crate::asset::hash(data) => Status::Unchanged,
+            _ => Status::Written,
+        };
+        match status {
+            Status::Unchanged => {
+                tracing::trace!("Skipping unchanged file; path={}", path.to_string_lossy())
             }
+            Status::Written if self.dry_run => tracing::info!(
+                "Would write file; path={}; len={}",
+                path.to_string_lossy(),
+                data.len()
+            ),
+            Status::Written => tracing::trace!(
+                "Writing file; path={}; len={}",
+                path.to_string_lossy(),
+                data.len()
+            ),
         }
-        path
+        self.report.files.push(FileReport {
+            path: path.to_path_buf(),
+            source: src.to_path_buf(),
+            size: data.len(),
+            status,
+            dependencies: vec![],
+            render_ms: None,
+        });
+        status
     }
 }
 
 impl Enqueuer {
-    pub fn file(&self, path: PathBuf, content: String) {
-        self.0.send(Command::Write(path, content)).unwrap();
+    pub fn file(&self, path: PathBuf, content: impl Into<Vec<u8>>, src: PathBuf) {
+        self.0
+            .send(Command::Write(path, content.into(), src))
+            .unwrap();
     }
 
     pub fn copy(&self, from: PathBuf, to: PathBuf) {
         self.0.send(Command::Copy(from, to)).unwrap();
     }
-
-    pub fn copy_maybe(&self, from: PathBuf, to: PathBuf) {
-        if !from.exists() || !from.is_file() {
-            return;
-        }
-        self.copy(from, to);
-    }
 }
 
 impl Drop for Writer {
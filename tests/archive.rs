@@ -0,0 +1,121 @@
+mod common;
+
+use common::Project;
+
+fn project() -> Project {
+    let project = Project::new("archive");
+    project
+        .file(
+            "config.ron",
+            r#"(
+    vars: {},
+    dist: "dist",
+    src: [
+        ("index.html", Html("index.html")),
+        ("static", Copy("static/*")),
+    ],
+)"#,
+        )
+        .file("index.html", "<p>Index</p>")
+        .file("static/b.txt", "b")
+        .file("static/a.txt", "a");
+    project
+}
+
+#[test]
+fn failed_builds_keep_the_previous_archive() {
+    for name in &["site.zip", "site.tar.gz"] {
+        let project = project();
+        project.ok(&["build", "--archive", name]);
+        let previous = std::fs::read(project.dir.join(name)).unwrap();
+        assert!(!previous.is_empty());
+
+        project.file(
+            "index.html",
+            r#"<super:include src="missing.html"></super:include>"#,
+        );
+        project.fails(&["build", "--archive", name]);
+        assert_eq!(std::fs::read(project.dir.join(name)).unwrap(), previous);
+        assert!(!project.exists(&format!(".{}.partial", name)));
+        assert!(!project.exists("dist"));
+    }
+}
+
+#[test]
+fn unknown_formats_are_rejected() {
+    let project = project();
+    let log = project.fails(&["build", "--archive", "site.rar"]);
+    assert!(log.contains("Unknown archive format"), "{}", log);
+    assert!(!project.exists("site.rar"));
+}
+
+/// Names and contents of a zip file, in archive order
+fn zip_entries(data: Vec<u8>) -> Vec<(String, String)> {
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+    (0..zip.len())
+        .map(|i| {
+            let mut file = zip.by_index(i).unwrap();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut file, &mut content).unwrap();
+            (file.name().to_string(), content)
+        })
+        .collect()
+}
+
+fn tar_entries(data: Vec<u8>) -> Vec<(String, String, u64)> {
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(&data[..]));
+    tar.entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            (name, content, entry.header().mtime().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn archives_contain_pages_and_copies_in_order() {
+    let project = project();
+    project.ok(&["build", "--archive", "site.zip"]);
+    assert_eq!(
+        zip_entries(std::fs::read(project.dir.join("site.zip")).unwrap()),
+        [
+            ("index.html".to_string(), "<p>Index</p>".to_string()),
+            ("static/a.txt".to_string(), "a".to_string()),
+            ("static/b.txt".to_string(), "b".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn archives_are_reproducible() {
+    let project = project();
+    let build = || {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_supersolid"))
+            .args(["build", "--archive", "site.tar.gz"])
+            .current_dir(&project.dir)
+            .env("SOURCE_DATE_EPOCH", "1614862800")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        std::fs::read(project.dir.join("site.tar.gz")).unwrap()
+    };
+    let first = build();
+    project.file("static/a.txt", "a");
+    assert_eq!(build(), first);
+    assert_eq!(
+        tar_entries(first),
+        [
+            (
+                "index.html".to_string(),
+                "<p>Index</p>".to_string(),
+                1_614_862_800
+            ),
+            ("static/a.txt".to_string(), "a".to_string(), 1_614_862_800),
+            ("static/b.txt".to_string(), "b".to_string(), 1_614_862_800),
+        ]
+    );
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn css_imports_are_inlined_with_rebased_urls() {
+    let files = render("assets");
+    assert_eq!(
+        page(&files, "site.css"),
+        "@import url(\"https://fonts.example.com/a.css\");\n\
+         \n\
+         /* @import \"parts/unused.css\"; */\n\
+         @media screen{\n\
+         .card { background: url(\"img/card.png\"); }\n\
+         }\n\
+         \n\
+         body { margin: 0; }\n"
+    );
+}
+
+#[test]
+fn bundles_are_minified() {
+    let files = render("assets");
+    assert_eq!(
+        page(&files, "site.min.css"),
+        "@import url(\"https://fonts.example.com/a.css\");@media screen{.card{background:url(\"img/card.png\")}}body{margin:0}"
+    );
+    assert_eq!(
+        page(&files, "site.js"),
+        "function util() {\nreturn /a\\/b/.test(\"a/b\");\n}\nlet a = 1\nlet b = 2\n"
+    );
+}
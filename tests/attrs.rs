@@ -0,0 +1,29 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn attributes_are_interpolated() {
+    let files = render("attrs");
+    let page = page(&files, "index.html");
+    assert!(page.contains(r#"<a href="/tags/rust.html" title="HELLO WORLD">"#));
+    assert!(page.contains(r#"<meta content="Hello World">"#));
+    // Include attributes, src included, are interpolated before they become variables
+    assert!(page.contains(r#"<div class="card"><h2>rust</h2>"#));
+}
+
+#[test]
+fn client_side_placeholders_are_kept() {
+    let files = render("attrs");
+    // A missing variable is left as is, `\{{` is a literal `{{`
+    assert!(
+        page(&files, "index.html").contains(r#"<p x-text="{{ count }}" data-raw="{{ tag }}"></p>"#)
+    );
+}
+
+#[test]
+fn set_attributes_replace_existing_ones_and_are_escaped() {
+    let files = render("attrs");
+    assert!(page(&files, "index.html")
+        .contains(r#"<img title="Say &quot;hi&quot; &amp; go" alt="Hello World">"#));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::Project;
+
+fn project() -> Project {
+    let project = Project::new("cli");
+    project
+        .file(
+            "site/config.ron",
+            r#"(
+    vars: { "TITLE": Text("Default") },
+    dist: "dist",
+    src: [("index.html", Html("index.html"))],
+)"#,
+        )
+        .file("site/index.html", r#"<h1 super:content="TITLE"></h1>"#);
+    project
+}
+
+#[test]
+fn project_dir_is_an_argument() {
+    let project = project();
+    project.ok(&["build", "site"]);
+    assert_eq!(project.read("site/dist/index.html"), "<h1>Default</h1>");
+}
+
+#[test]
+fn config_path_sets_the_project_dir() {
+    let project = project();
+    project
+        .file(
+            "other.ron",
+            r#"(vars: { "TITLE": Text("Other") }, dist: "public", src: [("index.html", Html("site/index.html"))])"#,
+        );
+    project.ok(&["build", "--config", "other.ron"]);
+    assert_eq!(project.read("public/index.html"), "<h1>Other</h1>");
+}
+
+#[test]
+fn out_overrides_dist() {
+    let project = project();
+    project.ok(&["build", "site", "--out", "out"]);
+    assert_eq!(project.read("out/index.html"), "<h1>Default</h1>");
+    assert!(!project.exists("site/dist"));
+}
+
+#[test]
+fn defines_override_vars() {
+    let project = project();
+    project.ok(&[
+        "build",
+        "site",
+        "-D",
+        "TITLE=Staging = 2",
+        "--define",
+        "UNUSED=",
+    ]);
+    assert_eq!(project.read("site/dist/index.html"), "<h1>Staging = 2</h1>");
+}
+
+#[test]
+fn invalid_defines_are_usage_errors() {
+    let project = project();
+    let output = project.run(&["build", "site", "-D", "=value"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected KEY=VALUE, got '=value'"));
+}
+
+#[test]
+fn missing_config_fails() {
+    let project = project();
+    let log = project.fails(&["build"]);
+    assert!(log.contains("No config file found"), "{}", log);
+    let log = project.fails(&["check", "site", "--config", "missing.ron"]);
+    assert!(log.contains("No config file found"), "{}", log);
+}
+
+#[test]
+fn quiet_only_logs_errors() {
+    let project = project();
+    assert_eq!(project.ok(&["build", "site", "--quiet"]), "");
+    assert!(project.ok(&["build", "site"]).contains("INFO"));
+}
+
+#[test]
+fn clean_removes_dist() {
+    let project = project();
+    project.ok(&["build", "site"]);
+    project.ok(&["clean", "site"]);
+    assert!(!project.exists("site/dist"));
+    assert!(project.exists("site/index.html"));
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::Project;
+
+fn colliding(policy: &str) -> Project {
+    let project = Project::new("collisions");
+    project
+        .file(
+            "config.ron",
+            format!(
+                r#"(
+    vars: {{}},
+    dist: "dist",
+    {}
+    src: [
+        ("index.html", Html("a.html")),
+        ("index.html", Html("b.html")),
+    ],
+)"#,
+                policy
+            ),
+        )
+        .file("a.html", "<p>a</p>")
+        .file("b.html", "<p>b</p>");
+    project
+}
+
+#[test]
+fn collisions_fail_the_build_by_default() {
+    let project = colliding("");
+    let log = project.fails(&["build"]);
+    assert!(log.contains("Output path collision; path=index.html; first=a.html; second=b.html"));
+    assert!(!project.exists("dist/index.html"));
+}
+
+#[test]
+fn collisions_can_be_allowed() {
+    let project = colliding("collisions: Warn,");
+    let log = project.ok(&["build"]);
+    assert!(log.contains("Output path collision, overwriting; path=index.html"));
+    assert_eq!(project.read("dist/index.html"), "<p>b</p>");
+}
@@ -0,0 +1,129 @@
+//! Helpers shared by the integration tests, not every test uses all of them
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use supersolid::cli::ProjectArgs;
+use supersolid::sink::MemorySink;
+use supersolid::writer::Target;
+
+/// Loading a project changes the working directory, so only one site is built at a time
+static BUILD: Mutex<()> = Mutex::new(());
+
+/// Builds `tests/sites/<name>` into memory, returns the output files by path
+pub fn render(name: &str) -> BTreeMap<PathBuf, String> {
+    let _lock = BUILD.lock().unwrap_or_else(|err| err.into_inner());
+    let args = ProjectArgs {
+        dir: Some(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/sites")
+                .join(name),
+        ),
+        config: None,
+        out: None,
+        defines: vec![],
+    };
+    let sink = MemorySink::default();
+    supersolid::build(&args, false, |_| Target::Memory(sink.clone()));
+    sink.files()
+        .into_iter()
+        .map(|(path, data)| (path, String::from_utf8(data).unwrap()))
+        .collect()
+}
+
+/// A generated page, fails if it wasn't built
+pub fn page<'a>(files: &'a BTreeMap<PathBuf, String>, path: &str) -> &'a str {
+    match files.get(Path::new(path)) {
+        Some(page) => page,
+        None => panic!("{} wasn't built, got {:?}", path, files.keys()),
+    }
+}
+
+/// A project in a temporary directory, built by running the binary
+pub struct Project {
+    pub dir: PathBuf,
+}
+
+impl Project {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "supersolid-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        Self { dir }
+    }
+
+    /// Writes a file, relative to the project dir
+    pub fn file(&self, path: &str, content: impl AsRef<[u8]>) -> &Self {
+        let path = self.dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+        self
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        match std::fs::read_to_string(self.dir.join(path)) {
+            Ok(content) => content,
+            Err(err) => panic!("Unable to read {}: {}", path, err),
+        }
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.dir.join(path).exists()
+    }
+
+    /// Runs supersolid in the project dir
+    pub fn run(&self, args: &[&str]) -> Output {
+        std::process::Command::new(env!("CARGO_BIN_EXE_supersolid"))
+            .args(args)
+            .current_dir(&self.dir)
+            .env("NO_COLOR", "1")
+            .env_remove("RUST_LOG")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs supersolid and fails the test if it doesn't succeed
+    pub fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        let log = String::from_utf8_lossy(&output.stdout).to_string()
+            + &String::from_utf8_lossy(&output.stderr);
+        assert!(
+            output.status.success(),
+            "supersolid {:?} failed:\n{}",
+            args,
+            log
+        );
+        log
+    }
+
+    /// Runs supersolid and fails the test if it succeeds, returns the log
+    pub fn fails(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        let log = String::from_utf8_lossy(&output.stdout).to_string()
+            + &String::from_utf8_lossy(&output.stderr);
+        assert!(
+            !output.status.success(),
+            "supersolid {:?} succeeded:\n{}",
+            args,
+            log
+        );
+        log
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
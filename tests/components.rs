@@ -0,0 +1,34 @@
+mod common;
+
+use common::{page, render, Project};
+
+#[test]
+fn component_names_are_case_insensitive() {
+    let files = render("components");
+    assert_eq!(
+        page(&files, "index.html"),
+        "<main><div class=\"card\">Tag</div>\n<div class=\"card\">Lower</div>\n<div class=\"card\">Component</div>\n<span class=\"badge\">new</span>\n</main>\n"
+    );
+}
+
+#[test]
+fn names_differing_in_case_are_rejected() {
+    let project = Project::new("components");
+    project
+        .file(
+            "config.ron",
+            r#"(
+    vars: {},
+    dist: "dist",
+    components: { "card": "card.html", "Card": "other.html" },
+    src: [("index.html", Html("index.html"))],
+)"#,
+        )
+        .file("index.html", "<p>Index</p>");
+    let log = project.fails(&["build"]);
+    assert!(
+        log.contains("Component registered twice, names are case insensitive; name=card"),
+        "{}",
+        log
+    );
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::Project;
+
+fn project(keep: &str) -> Project {
+    let project = Project::new("dist");
+    project
+        .file(
+            "config.ron",
+            format!(
+                r#"(
+    vars: {{}},
+    dist: "dist",
+    keep: [{}],
+    src: [
+        ("index.html", Html("index.html")),
+        ("downloads", Copy("downloads/*")),
+    ],
+)"#,
+                keep
+            ),
+        )
+        .file("index.html", "<p>Index</p>")
+        .file("downloads/a.txt", "a")
+        .file("downloads/b.txt", "b");
+    project
+}
+
+#[test]
+fn foreign_files_survive_a_build() {
+    let project = project("");
+    project
+        .file("dist/CNAME", "example.com")
+        .file("dist/.git/HEAD", "ref: refs/heads/main");
+    project.ok(&["build"]);
+    project.ok(&["build"]);
+    assert_eq!(project.read("dist/CNAME"), "example.com");
+    assert_eq!(project.read("dist/.git/HEAD"), "ref: refs/heads/main");
+    assert_eq!(project.read("dist/index.html"), "<p>Index</p>");
+}
+
+#[test]
+fn unchanged_files_are_not_rewritten() {
+    let project = project("");
+    let log = project.ok(&["build"]);
+    assert!(
+        log.contains("Synced output; written=3; unchanged=0; removed=0"),
+        "{}",
+        log
+    );
+    project.file("index.html", "<p>Changed</p>");
+    let log = project.ok(&["build"]);
+    assert!(
+        log.contains("Synced output; written=1; unchanged=2; removed=0"),
+        "{}",
+        log
+    );
+    assert_eq!(project.read("dist/index.html"), "<p>Changed</p>");
+}
+
+#[test]
+fn stale_files_are_removed() {
+    let project = project("");
+    project.ok(&["build"]);
+    std::fs::remove_file(project.dir.join("downloads/b.txt")).unwrap();
+    let log = project.ok(&["build"]);
+    assert!(log.contains("removed=1"), "{}", log);
+    assert!(project.exists("dist/downloads/a.txt"));
+    assert!(!project.exists("dist/downloads/b.txt"));
+    // Only files of the previous build count as stale
+    assert!(!project.read("dist/.supersolid").contains("b.txt"));
+}
+
+#[test]
+fn kept_files_are_never_removed() {
+    let project = project(r#""downloads/b.*""#);
+    project.ok(&["build"]);
+    std::fs::remove_file(project.dir.join("downloads/a.txt")).unwrap();
+    std::fs::remove_file(project.dir.join("downloads/b.txt")).unwrap();
+    project.ok(&["build"]);
+    assert!(!project.exists("dist/downloads/a.txt"));
+    assert_eq!(project.read("dist/downloads/b.txt"), "b");
+}
+
+#[test]
+fn invalid_keep_patterns_fail() {
+    let project = project(r#""[""#);
+    let log = project.fails(&["build"]);
+    assert!(log.contains("Invalid keep pattern; pattern=["), "{}", log);
+}
+
+#[test]
+fn failed_builds_leave_dist_untouched() {
+    let project = project("");
+    project.ok(&["build"]);
+    project
+        .file(
+            "index.html",
+            r#"<super:include src="missing.html"></super:include>"#,
+        )
+        .file("downloads/c.txt", "c");
+    project.fails(&["build"]);
+    assert_eq!(project.read("dist/index.html"), "<p>Index</p>");
+    assert!(!project.exists("dist/downloads/c.txt"));
+
+    project.file("index.html", "<p>Fixed</p>");
+    let log = project.ok(&["build"]);
+    assert!(
+        log.contains("Removing staging dir left behind by a failed build"),
+        "{}",
+        log
+    );
+    assert_eq!(project.read("dist/index.html"), "<p>Fixed</p>");
+    assert!(!project.exists(".dist.staging"));
+}
+
+#[test]
+fn builds_leave_nothing_behind() {
+    let project = project("");
+    project.ok(&["build"]);
+    project.file("index.html", "<p>Changed</p>");
+    project.ok(&["build"]);
+    let mut entries = std::fs::read_dir(&project.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    entries.sort();
+    assert_eq!(entries, ["config.ron", "dist", "downloads", "index.html"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_dist_is_pointed_to_the_new_build() {
+    let project = project("");
+    project.file("releases/manual/index.html", "<p>Manual</p>");
+    std::os::unix::fs::symlink("releases/manual", project.dir.join("dist")).unwrap();
+    project.ok(&["build"]);
+    let target = std::fs::read_link(project.dir.join("dist")).unwrap();
+    assert!(
+        target.to_string_lossy().starts_with(".dist."),
+        "{:?}",
+        target
+    );
+    assert_eq!(project.read("dist/index.html"), "<p>Index</p>");
+    // A directory the link was pointed to by hand is left alone
+    assert_eq!(project.read("releases/manual/index.html"), "<p>Manual</p>");
+
+    project.file("index.html", "<p>Changed</p>");
+    project.ok(&["build"]);
+    assert_eq!(project.read("dist/index.html"), "<p>Changed</p>");
+    // The previous build is removed once the link points to the next one
+    assert!(!project.dir.join(&target).exists());
+}
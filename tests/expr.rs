@@ -0,0 +1,24 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn expressions_are_evaluated() {
+    let files = render("expr");
+    let page = page(&files, "index.html");
+    assert!(page.contains(r#"<p id="math">9</p>"#));
+    assert!(page.contains(r#"<p id="slug">hello-world</p>"#));
+    assert!(page.contains(r#"<p id="date">04 March 2021</p>"#));
+    assert!(page.contains(r#"<p id="default">none</p>"#));
+    assert!(page.contains(r#"<p id="truncate">Hello...</p>"#));
+}
+
+#[test]
+fn conditions_use_truthiness() {
+    let files = render("expr");
+    let page = page(&files, "index.html");
+    assert!(page.contains("<p>many</p>"));
+    assert!(page.contains("<p>not missing</p>"));
+    // "0" is falsy
+    assert!(!page.contains("zero"));
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn copied_files_get_hashed_names() {
+    let files = render("fingerprint");
+    assert!(files.contains_key(std::path::Path::new("static/site.eac0e790573fb642.css")));
+    assert!(files.contains_key(std::path::Path::new("manifest.7830990acef7bebc.json")));
+    assert!(!files.contains_key(std::path::Path::new("static/site.css")));
+}
+
+#[test]
+fn references_are_rewritten() {
+    let files = render("fingerprint");
+    for path in &["index.html", "blog/post.html"] {
+        let html = page(&files, path);
+        assert!(html.contains(r#"<link rel="manifest" href="/manifest.7830990acef7bebc.json">"#));
+        // The query is kept, only the path changes
+        assert!(html.contains(r#"href="/static/site.eac0e790573fb642.css?v=1""#));
+        assert!(html.contains(r#"href="https://example.com/static/site.css""#));
+    }
+}
+
+#[test]
+fn asset_manifest_does_not_clash_with_a_web_app_manifest() {
+    let files = render("fingerprint");
+    assert_eq!(
+        page(&files, "supersolid-manifest.json"),
+        r#"{
+  "/manifest.json": "/manifest.7830990acef7bebc.json",
+  "/static/site.css": "/static/site.eac0e790573fb642.css"
+}"#
+    );
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn content_is_escaped_text() {
+    let files = render("html");
+    let page = page(&files, "index.html");
+    assert!(page.contains(r#"<p id="content">&lt;b&gt;bold&lt;/b&gt;</p>"#));
+    assert!(page.contains(r#"<div id="md-content">&lt;p&gt;&lt;em&gt;short&lt;/em&gt;&lt;/p&gt;"#));
+}
+
+#[test]
+fn html_is_parsed_as_markup() {
+    let files = render("html");
+    let page = page(&files, "index.html");
+    assert!(page.contains(r#"<p id="html"><b>bold</b></p>"#));
+    assert!(page.contains(r#"<div id="md-html"><p><em>short</em></p>"#));
+}
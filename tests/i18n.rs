@@ -0,0 +1,56 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn languages_are_written_to_their_own_directory() {
+    let files = render("i18n");
+    let html = files
+        .keys()
+        .filter(|path| path.extension() == Some("html".as_ref()))
+        .map(|path| path.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        html,
+        [
+            "de/index.html",
+            "de/recipes/pizza.html",
+            "de/recipes/suppe.html",
+            "index.html",
+            "recipes/pizza.html",
+        ]
+    );
+}
+
+#[test]
+fn strings_and_lang_are_variables() {
+    let files = render("i18n");
+    let en = page(&files, "index.html");
+    assert!(en.contains(r#"<html lang="en"><head><title>Hello</title>"#));
+    assert!(en.contains("<p>en</p>"));
+    let de = page(&files, "de/index.html");
+    assert!(de.contains(r#"<html lang="de"><head><title>Hallo</title>"#));
+    assert!(de.contains("<p>de</p>"));
+    assert!(
+        page(&files, "de/recipes/pizza.html").contains("<p>Hallo</p><h1>Pizza auf Deutsch</h1>")
+    );
+}
+
+#[test]
+fn translations_link_each_other() {
+    let files = render("i18n");
+    let links = r#"<link rel="alternate" hreflang="de" href="/de/recipes/pizza.html"><link rel="alternate" hreflang="en" href="/recipes/pizza.html">"#;
+    assert!(page(&files, "recipes/pizza.html").contains(links));
+    assert!(page(&files, "de/recipes/pizza.html").contains(links));
+}
+
+#[test]
+fn front_matter_sets_the_language() {
+    let files = render("i18n");
+    let suppe = page(&files, "de/recipes/suppe.html");
+    assert!(
+        suppe.contains(r#"<html lang="de"><head><title>Suppe</title></head><body><p>Hallo</p>"#)
+    );
+    // Without a translation there is nothing to link to
+    assert!(!suppe.contains("hreflang"));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::Project;
+use std::io::Cursor;
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let img = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([x as u8, y as u8, 128]));
+    let mut data = Cursor::new(vec![]);
+    img.write_to(&mut data, image::ImageFormat::Png).unwrap();
+    data.into_inner()
+}
+
+fn project() -> Project {
+    let project = Project::new("images");
+    project
+        .file(
+            "config.ron",
+            r#"(
+    vars: {},
+    dist: "dist",
+    images: Some((widths: [8, 40], formats: [Webp], sizes: "50vw")),
+    src: [
+        ("photos", Copy("photos/*")),
+        ("index.html", Html("index.html")),
+    ],
+)"#,
+        )
+        .file("photos/pic.png", png(20, 10))
+        .file("hero.png", png(30, 10))
+        .file(
+            "index.html",
+            r#"<img src="photos/pic.png" alt="Copied"><super:image src="hero.png" alt="Included"></super:image>"#,
+        );
+    project
+}
+
+/// Url of a variant without the width, the hash depends on how the png encoder wrote the test image
+fn variant<'a>(html: &'a str, prefix: &str) -> &'a str {
+    let start = html.find(prefix).expect("no variants");
+    &html[start..start + prefix.len() + 8]
+}
+
+#[test]
+fn copied_images_get_variants_next_to_them() {
+    let project = project();
+    project.ok(&["build"]);
+    let html = project.read("dist/index.html");
+    let pic = variant(&html, "/photos/pic-");
+    assert!(html.starts_with(&format!(
+        r#"<picture><source type="image/webp" srcset="{0}-8.webp 8w, {0}-20.webp 20w" sizes="50vw"><img src="photos/pic.png" alt="Copied" width="20" height="10" sizes="50vw" srcset="{0}-8.png 8w, {0}-20.png 20w"></picture>"#,
+        pic
+    )));
+    for name in &["-8.webp", "-20.webp", "-8.png", "-20.png"] {
+        let path = format!("dist{}{}", pic, name);
+        assert!(project.exists(&path), "{} is missing", path);
+    }
+    assert!(project.exists("dist/photos/pic.png"));
+    assert!(project.exists(".cache/images"));
+}
+
+#[test]
+fn included_images_are_written_to_out() {
+    let project = project();
+    project.ok(&["build"]);
+    let html = project.read("dist/index.html");
+    let hero = variant(&html, "/images/hero-");
+    assert!(html.ends_with(&format!(
+        r#"<picture><source type="image/webp" srcset="{0}-8.webp 8w, {0}-30.webp 30w" sizes="50vw"><img alt="Included" src="{0}-30.png" width="30" height="10" sizes="50vw" srcset="{0}-8.png 8w, {0}-30.png 30w"></picture>"#,
+        hero
+    )));
+    assert!(project.exists(&format!("dist{}-30.webp", hero)));
+    assert!(!project.exists("dist/hero.png"));
+}
+
+#[test]
+fn dry_runs_and_check_leave_the_cache_alone() {
+    let project = project();
+    project.ok(&["build", "--dry-run"]);
+    project.ok(&["check"]);
+    assert!(!project.exists(".cache"));
+    assert!(!project.exists("dist"));
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn includes_and_components_get_attributes_and_children() {
+    let files = render("includes");
+    assert_eq!(
+        page(&files, "include.html"),
+        "<main><div class=\"card\"><h2>Pizza</h2><p>Round</p></div>\n<div class=\"card\"><h2>Pasta</h2><p>No description</p></div>\n<aside class=\"info\"><p>Careful</p></aside>\n</main>\n"
+    );
+}
+
+#[test]
+fn components_can_be_nested_in_themselves() {
+    let files = render("includes");
+    assert_eq!(
+        page(&files, "nested.html"),
+        "<main><aside class=\"outer\"><aside class=\"inner\"><p>Deep</p></aside>\n</aside>\n</main>\n"
+    );
+}
+
+#[test]
+fn children_see_the_variables_of_their_page() {
+    let files = render("includes");
+    // The attributes of the include are only variables inside the included file
+    assert_eq!(
+        page(&files, "context.html"),
+        "<main><div class=\"card\"><h2>Pizza</h2><h3>Page title</h3><p class=\"Page title\">Page title</p></div>\n</main>\n"
+    );
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn markdown_text_is_interpolated_when_enabled() {
+    let files = render("interpolate");
+    let page = page(&files, "posts/on.html");
+    assert!(page.contains("<p>Serves 8 on Recipes, {{ missing }} stays.</p>"));
+    assert!(page.contains("<p><code>{{ servings }}</code></p>"));
+}
+
+#[test]
+fn escaped_placeholders_are_literal() {
+    let files = render("interpolate");
+    assert!(page(&files, "posts/on.html")
+        .contains("<p>Literal {{ servings }} and {{&lt; note &gt;}}, a backslash \\4.</p>"));
+}
+
+#[test]
+fn markdown_text_is_left_alone_by_default() {
+    let files = render("interpolate");
+    assert!(page(&files, "posts/off.html")
+        .contains("<p>Serves {{ servings }}, escaped {{ servings }}.</p>"));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{page, render, Project};
+
+#[test]
+fn slots_are_placed_by_name() {
+    let files = render("layouts");
+    let page = page(&files, "slots.html");
+    assert_eq!(
+        page,
+        "<!DOCTYPE html><html><head><link rel=\"stylesheet\" href=\"/page.css\"></head>\n<body>\n\n\n<p>Page content</p>\n<footer><p>Page footer</p></footer>\n\n</body></html>"
+    );
+}
+
+#[test]
+fn missing_slots_use_the_default_content() {
+    let files = render("layouts");
+    let page = page(&files, "defaults.html");
+    assert!(page.contains("<head></head>"));
+    assert!(page.contains("<p>Only content</p>\n<footer><p>Default footer</p></footer>"));
+}
+
+#[test]
+fn layouts_can_wrap_layouts() {
+    let files = render("layouts");
+    let page = page(&files, "nested.html");
+    assert!(page.contains(
+        r#"<head><link rel="stylesheet" href="/layout.css"><link rel="stylesheet" href="/page.css"></head>"#
+    ));
+    assert!(page.contains("<article>\n\n<p>Page content</p>\n</article>"));
+    assert!(page.contains("<footer><p>Default footer</p></footer>"));
+    assert!(!page.contains("super:"));
+}
+
+#[test]
+fn pages_override_slots_of_layouts() {
+    let files = render("layouts");
+    let page = page(&files, "override.html");
+    assert!(page.contains(r#"<head><link rel="stylesheet" href="/only.css"></head>"#));
+    // The parent of a slot only the template fills is its default content
+    assert!(page.contains("<footer><p>Default footer</p><p>Extra footer</p></footer>"));
+}
+
+#[test]
+fn template_cycles_fail_the_build() {
+    let project = Project::new("layouts");
+    project
+        .file(
+            "config.ron",
+            r#"(vars: {}, dist: "dist", src: [("index.html", Html("page.html"))])"#,
+        )
+        .file(
+            "page.html",
+            r#"<super:wrap src="a.html"><p>Page</p></super:wrap>"#,
+        )
+        .file(
+            "a.html",
+            r#"<super:wrap src="./b.html"><super:content></super:content></super:wrap>"#,
+        )
+        .file(
+            "b.html",
+            r#"<div><super:include src="a.html"></super:include></div>"#,
+        );
+    let log = project.fails(&["build"]);
+    assert!(
+        log.contains("Template cycle found; chain=page.html -> a.html -> ./b.html -> a.html"),
+        "{}",
+        log
+    );
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{page, render, Project};
+
+#[test]
+fn include_options_select_lines_and_format() {
+    let files = render("options");
+    assert_eq!(
+        page(&files, "index.html"),
+        concat!(
+            "<div><pre><code class=\"language-rust\">fn b() -&gt; Vec&lt;u8&gt; { vec![] }\nfn c() {}\n</code></pre>",
+            "fn d() {}\n",
+            "<h1>Notes</h1>\n",
+            "<p id=\"lang\">en</p></div>",
+        )
+    );
+}
+
+#[test]
+fn invalid_line_ranges_fail() {
+    let project = Project::new("options");
+    project
+        .file(
+            "config.ron",
+            r#"(vars: {}, dist: "dist", src: [("index.html", Html("index.html"))])"#,
+        )
+        .file("code.rs", "fn a() {}\n")
+        .file(
+            "index.html",
+            r#"<super:include src="code.rs" lines="3-2"></super:include>"#,
+        );
+    let log = project.fails(&["build"]);
+    assert!(
+        log.contains("Invalid line range; lines=3-2; path=index.html"),
+        "{}",
+        log
+    );
+}
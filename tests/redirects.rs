@@ -0,0 +1,34 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn redirect_pages_are_written() {
+    let files = render("redirects");
+    let old = page(&files, "old.html");
+    assert!(old.contains(r#"<meta http-equiv="refresh" content="0; url=/new.html">"#));
+    assert!(old.contains(r#"<link rel="canonical" href="/new.html">"#));
+    // Paths without an extension become directories, urls are escaped
+    let gone = page(&files, "gone/index.html");
+    assert!(gone.contains(r#"href="https://example.com/?a=1&amp;b=2""#));
+}
+
+#[test]
+fn aliases_redirect_to_their_page() {
+    let files = render("redirects");
+    assert!(page(&files, "posts/first.html").contains("<h1>First</h1>"));
+    assert!(page(&files, "moved.html").contains("url=/posts/first.html"));
+    assert!(page(&files, "archive/first/index.html").contains("url=/posts/first.html"));
+}
+
+#[test]
+fn redirect_map_is_sorted() {
+    let files = render("redirects");
+    assert_eq!(
+        page(&files, "_redirects"),
+        "/archive/first/ /posts/first.html 301\n\
+         /gone/ https://example.com/?a=1&b=2 301\n\
+         /moved.html /posts/first.html 301\n\
+         /old.html /new.html 301\n"
+    );
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::Project;
+use serde_json::Value;
+
+fn project() -> Project {
+    let project = Project::new("report");
+    project
+        .file(
+            "config.ron",
+            r#"(
+    vars: {},
+    dist: "dist",
+    src: [
+        ("index.html", Html("index.html")),
+        ("static", Copy("static/*")),
+    ],
+)"#,
+        )
+        .file(
+            "index.html",
+            r#"<super:wrap src="base.html"><p>Index</p></super:wrap>"#,
+        )
+        .file("base.html", "<main><super:content></super:content></main>")
+        .file("static/a.txt", "a")
+        .file("static/b.txt", "b");
+    project
+}
+
+fn report(project: &Project, args: &[&str]) -> Value {
+    let mut args = args.to_vec();
+    args.extend(["--report", "report.json"]);
+    project.ok(&args);
+    serde_json::from_str(&project.read("report.json")).unwrap()
+}
+
+#[test]
+fn report_lists_every_file() {
+    let project = project();
+    let report = report(&project, &["build"]);
+    assert_eq!(report["dry_run"], false);
+    let files = report["files"].as_array().unwrap();
+    let index = files
+        .iter()
+        .find(|file| file["path"] == "index.html")
+        .unwrap();
+    assert_eq!(index["source"], "index.html");
+    assert_eq!(index["size"], "<main><p>Index</p></main>".len());
+    assert_eq!(index["status"], "written");
+    assert_eq!(index["dependencies"], serde_json::json!(["base.html"]));
+    assert!(index["render_ms"].is_f64());
+
+    let copied = files
+        .iter()
+        .find(|file| file["path"] == "static/a.txt")
+        .unwrap();
+    assert_eq!(copied["source"], "static/a.txt");
+    assert_eq!(copied["size"], 1);
+    // Only pages have dependencies and a render time
+    assert!(copied.get("dependencies").is_none());
+    assert!(copied.get("render_ms").is_none());
+}
+
+#[test]
+fn report_shows_unchanged_and_removed_files() {
+    let project = project();
+    project.ok(&["build"]);
+    std::fs::remove_file(project.dir.join("static/b.txt")).unwrap();
+    let report = report(&project, &["build"]);
+    assert!(report["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|file| file["status"] == "unchanged"));
+    assert_eq!(report["removed"], serde_json::json!(["static/b.txt"]));
+}
+
+#[test]
+fn dry_runs_touch_nothing() {
+    let project = project();
+    project.ok(&["build"]);
+    project.file("index.html", "<p>Changed</p>");
+    std::fs::remove_file(project.dir.join("static/b.txt")).unwrap();
+
+    let report = report(&project, &["build", "--dry-run"]);
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["removed"], serde_json::json!(["static/b.txt"]));
+    assert_eq!(project.read("dist/index.html"), "<main><p>Index</p></main>");
+    assert!(project.exists("dist/static/b.txt"));
+    assert!(!project.exists(".dist.staging"));
+
+    let log = project.ok(&["build", "--dry-run"]);
+    assert!(
+        log.contains("Would write file; path=index.html; len=14"),
+        "{}",
+        log
+    );
+    assert!(
+        log.contains("Would remove stale file; path=static/b.txt"),
+        "{}",
+        log
+    );
+    assert!(
+        log.contains("Dry run, nothing written; written=1; unchanged=1; removed=1"),
+        "{}",
+        log
+    );
+}
+
+#[test]
+fn dry_runs_without_dist_create_nothing() {
+    let project = project();
+    project.ok(&["build", "--dry-run"]);
+    assert!(!project.exists("dist"));
+}
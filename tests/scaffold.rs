@@ -0,0 +1,59 @@
+mod common;
+
+use common::Project;
+
+/// Creates a starter and builds it, the build must not warn about anything
+fn build(starter: &str) -> Project {
+    let project = Project::new("scaffold");
+    project.ok(&["new", "site", "--template", starter]);
+    let log = project.ok(&["build", "site"]);
+    assert!(!log.contains("WARN"), "{}", log);
+    project
+}
+
+#[test]
+fn blog_starter_builds() {
+    let project = build("blog");
+    let index = project.read("site/dist/index.html");
+    assert!(index.contains(r#"<a href="/posts/hello-world.html">Hello, world</a>"#));
+    assert!(index.contains(r#"<a href="/posts/writing-posts.html">Writing posts</a>"#));
+    assert!(project.exists("site/dist/static/style.css"));
+    assert!(project.read("site/.gitignore").contains("dist/"));
+}
+
+#[test]
+fn recipes_starter_builds() {
+    let project = build("recipes");
+    let index = project.read("site/dist/index.html");
+    assert!(index.contains(r#"<a href="/recipes/pancakes.html">Pancakes</a>"#));
+    assert!(index.contains(r#"<a href="/recipes/tomato-soup.html">Tomato soup</a>"#));
+    assert!(project.exists("site/dist/recipes/pancakes.html"));
+}
+
+#[test]
+fn blog_is_the_default() {
+    let project = Project::new("scaffold");
+    project.ok(&["new", "site"]);
+    assert!(project.exists("site/src/posts/hello-world.md"));
+}
+
+#[test]
+fn existing_files_are_not_overwritten() {
+    let project = Project::new("scaffold");
+    project.file("site/config.ron", "mine");
+    let log = project.fails(&["new", "site"]);
+    assert!(
+        log.contains("Path already exists and is not empty"),
+        "{}",
+        log
+    );
+    assert_eq!(project.read("site/config.ron"), "mine");
+}
+
+#[test]
+fn empty_directories_can_be_used() {
+    let project = Project::new("scaffold");
+    std::fs::create_dir(project.dir.join("site")).unwrap();
+    project.ok(&["new", "site", "-t", "recipes"]);
+    assert!(project.exists("site/config.ron"));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::Project;
+
+fn project(options: &str) -> Project {
+    let project = Project::new("scss");
+    project
+        .file(
+            "config.ron",
+            format!(
+                r#"(
+    vars: {{}},
+    dist: "dist",
+    src: [
+        ("css/main.css", Scss(src: "scss/main.scss", load_paths: ["scss/vendor"], {})),
+    ],
+)"#,
+                options
+            ),
+        )
+        .file(
+            "scss/main.scss",
+            "@use 'colors';\n\nbody {\n  a { color: colors.$link; }\n}\n",
+        )
+        .file("scss/vendor/_colors.scss", "$link: #00f;\n");
+    project
+}
+
+#[test]
+fn scss_is_compiled_with_load_paths() {
+    let project = project("");
+    project.ok(&["build"]);
+    assert_eq!(
+        project.read("dist/css/main.css"),
+        "body a {\n  color: #00f;\n}\n"
+    );
+}
+
+#[test]
+fn scss_can_be_minified() {
+    let project = project("minify: true");
+    project.ok(&["build"]);
+    assert_eq!(project.read("dist/css/main.css"), "body a{color:blue}");
+}
+
+#[test]
+fn compile_errors_fail_the_build() {
+    let project = project("");
+    project.file("scss/main.scss", "body { color: $missing; }\n");
+    let log = project.fails(&["build"]);
+    assert!(
+        log.contains("Unable to compile scss; path=scss/main.scss"),
+        "{}",
+        log
+    );
+    assert!(!project.exists("dist"));
+}
+
+#[test]
+fn source_maps_are_rejected() {
+    let project = project("source_map: true");
+    let log = project.fails(&["build"]);
+    assert!(log.contains("Scss source maps aren't supported"), "{}", log);
+}
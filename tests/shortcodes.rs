@@ -0,0 +1,31 @@
+mod common;
+
+use common::{page, render};
+
+#[test]
+fn shortcodes_expand_to_components() {
+    let files = render("shortcodes");
+    // Shortcodes on their own, even when spanning lines or paragraphs, aren't wrapped in a paragraph
+    assert_eq!(
+        page(&files, "shortcodes.html"),
+        concat!(
+            "<p>Intro text.</p>\n",
+            "<aside class=\"warn\">\n",
+            "Be <strong>careful</strong>.\n",
+            "</aside>\n",
+            "\n",
+            "<p>Inline <span class=\"badge\">new</span>\n",
+            " here and <code>{{&lt; badge &gt;}}</code> in code.</p>\n",
+            "<aside class=\"tip\">Same line <strong>block</strong></aside>\n",
+            "<span class=\"badge\">solo</span>\n",
+            "\n",
+            "<p><aside class=\"a\">One</aside>\n",
+            " and <aside class=\"b\">two</aside>\n",
+            "</p>\n",
+            "<aside class=\"multi\">\n",
+            "<p>First paragraph.</p>\n",
+            "<span class=\"badge\">inner</span>\n",
+            "</aside>\n",
+        )
+    );
+}
@@ -0,0 +1,5 @@
+import "./util.js";
+// main
+let a = 1 /* first
+*/
+let b = 2
@@ -0,0 +1,3 @@
+function util() {
+    return /a\/b/.test("a/b");
+}
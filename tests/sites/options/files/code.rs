@@ -0,0 +1,4 @@
+fn a() {}
+fn b() -> Vec<u8> { vec![] }
+fn c() {}
+fn d() {}